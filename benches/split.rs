@@ -0,0 +1,79 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use kustomize_upstream::{split_manifests, Config};
+use std::fmt::Write;
+use std::fs;
+
+// resources split per iteration, enough for the renders to dominate
+const RESOURCES: usize = 3000;
+
+// renders every resource with the path, filename and metadata templates
+const CONFIG: &str = r#"
+Top:
+  name: bench
+  version: 1.0.0
+  sourceTemplate: https://example.com/{{top.name}}/{{top.version}}/install.yaml
+DefaultPackageSpec:
+  template: |
+    apiVersion: kustomize.config.k8s.io/v1beta1
+    kind: Kustomization
+    resources:
+    {% for resource in package.resources %}  - {{resource.filename}}
+    {% endfor %}
+  pathTemplate: "{{top.name}}/{{packageName}}"
+  filenameTemplate: kustomization.yaml
+  defaultName: misc
+  resourceSpec:
+    pathTemplate: "{{top.name}}/{{packageName}}"
+    filenameTemplate: "{{resource.kind | kebab}}-{{resource.name}}.yaml"
+SplitRules:
+  - matcher:
+      kind: ConfigMap
+    packageName: "config-{{resource.namespace}}"
+    labels:
+      app.kubernetes.io/version: "{{top.version}}"
+  - matcher:
+      kind: Service
+    packageName: services
+"#;
+
+fn config() -> Config {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.yaml");
+    fs::write(&path, CONFIG).unwrap();
+    Config::load(&path, None, &[]).unwrap()
+}
+
+fn manifests() -> String {
+    let mut manifests = String::new();
+    for idx in 0..RESOURCES {
+        let kind = ["ConfigMap", "Service", "ServiceAccount"][idx % 3];
+        writeln!(
+            manifests,
+            "---\napiVersion: v1\nkind: {}\nmetadata:\n  name: resource-{}\n  namespace: ns-{}\n  labels:\n    app: bench",
+            kind,
+            idx,
+            idx % 10
+        )
+        .unwrap();
+    }
+    manifests
+}
+
+// the same split with the templates compiled once and parsed per render
+fn split(c: &mut Criterion) {
+    let manifests = manifests();
+    let compiled = config();
+    let uncompiled = compiled.without_compiled_templates().unwrap();
+    let mut group = c.benchmark_group("split_manifests");
+    group.sample_size(10);
+    group.bench_function("compiled templates", |b| {
+        b.iter(|| split_manifests(manifests.as_bytes(), &compiled).unwrap())
+    });
+    group.bench_function("templates parsed per render", |b| {
+        b.iter(|| split_manifests(manifests.as_bytes(), &uncompiled).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, split);
+criterion_main!(benches);
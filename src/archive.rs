@@ -0,0 +1,58 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+// writes the generated files into a single archive, the format follows the
+// extension of the archive path, .tar.gz, .tgz or .zip
+pub fn write(archive: &Path, files: &[(PathBuf, Vec<u8>)]) -> std::io::Result<()> {
+    let name = archive.to_string_lossy();
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        write_tar_gz(archive, files)
+    } else if name.ends_with(".zip") {
+        write_zip(archive, files).map_err(std::io::Error::from)
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "unknown archive format, use .tar.gz, .tgz or .zip",
+        ))
+    }
+}
+
+fn write_tar_gz(archive: &Path, files: &[(PathBuf, Vec<u8>)]) -> std::io::Result<()> {
+    let encoder = GzEncoder::new(File::create(archive)?, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for (path, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, entry_path(path), content.as_slice())?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+fn write_zip(archive: &Path, files: &[(PathBuf, Vec<u8>)]) -> zip::result::ZipResult<()> {
+    let mut writer = zip::ZipWriter::new(File::create(archive)?);
+    let options = zip::write::FileOptions::default().unix_permissions(0o644);
+    for (path, content) in files {
+        let entry = entry_path(path);
+        let names: Vec<String> = entry
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        writer.start_file(names.join("/"), options)?;
+        writer.write_all(content)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+// path inside the archive, without a root or leading parent directories
+pub fn entry_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
@@ -0,0 +1,116 @@
+// replaces the value of Top.version in the text of a yaml, toml or json
+// config, leaving everything else including comments and quoting untouched,
+// returns the new text and the replaced version
+pub fn set_version(config: &str, extension: Option<&str>, version: &str) -> Result<(String, String), String> {
+    let (start, end) = match extension {
+        Some("toml") => toml_version(config),
+        Some("json") => json_version(config),
+        _ => yaml_version(config),
+    }
+    .ok_or_else(|| "the config sets no Top.version".to_string())?;
+    let old = config[start..end].to_string();
+    Ok((format!("{}{}{}", &config[..start], version, &config[end..]), old))
+}
+
+// byte range of the scalar after `version:` in the block below `Top:`
+fn yaml_version(config: &str) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    let mut in_top = false;
+    let mut indent = None;
+    for line in config.split_inclusive('\n') {
+        let line_offset = offset;
+        offset += line.len();
+        let content = line.trim_end();
+        let trimmed = content.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let line_indent = content.len() - trimmed.len();
+        if line_indent == 0 {
+            in_top = trimmed.starts_with("Top:");
+            continue;
+        }
+        // only direct children of Top, e.g. not Top.versions entries
+        if !in_top || *indent.get_or_insert(line_indent) != line_indent {
+            continue;
+        }
+        if let Some(value) = trimmed.strip_prefix("version:") {
+            let value_start = line_offset + line_indent + "version:".len() + (value.len() - value.trim_start().len());
+            let value = value.trim_start();
+            let scalar = match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => &value[1..value[1..].find(quote)? + 1],
+                _ => value.split(" #").next().unwrap_or_default().trim_end(),
+            };
+            let quoted = value.starts_with(['"', '\'']) as usize;
+            return Some((value_start + quoted, value_start + quoted + scalar.len()));
+        }
+    }
+    None
+}
+
+// byte range of the string after `version =` in the [Top] table
+fn toml_version(config: &str) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    let mut in_top = false;
+    for line in config.split_inclusive('\n') {
+        let line_offset = offset;
+        offset += line.len();
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_top = trimmed == "[Top]";
+            continue;
+        }
+        if !in_top {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) if key.trim() == "version" => (key, value),
+            _ => continue,
+        };
+        let value_offset = line_offset + key.len() + 1;
+        let start = value.find('"')? + 1;
+        let end = start + value[start..].find('"')?;
+        return Some((value_offset + start, value_offset + end));
+    }
+    None
+}
+
+// byte range of the string of "version" in the "Top" object, which holds no
+// nested objects
+fn json_version(config: &str) -> Option<(usize, usize)> {
+    let top = config.find("\"Top\"")?;
+    let object_start = top + config[top..].find('{')?;
+    let object_end = object_start + config[object_start..].find('}')?;
+    let key = object_start + config[object_start..object_end].find("\"version\"")?;
+    let after_key = key + "\"version\"".len();
+    let start = after_key + config[after_key..object_end].find('"')? + 1;
+    let end = start + config[start..object_end].find('"')?;
+    Some((start, end))
+}
+
+// the latest release of the github project the source template points to,
+// with or without the leading v as the current version
+pub fn latest_release(source_template: &str, current: &str) -> Result<String, String> {
+    let segments: Vec<&str> = source_template.split('/').filter(|segment| !segment.is_empty()).collect();
+    let (owner, repo) = match segments.get(1) {
+        Some(&"github.com") | Some(&"raw.githubusercontent.com") if segments.len() > 3 => (segments[2], segments[3]),
+        _ => return Err(format!("unable to find the latest release of {}, use --to", source_template)),
+    };
+    let url = format!("https://api.github.com/repos/{}/{}/releases/latest", owner, repo);
+    let body = reqwest::blocking::Client::new()
+        .get(&url)
+        .header(reqwest::header::USER_AGENT, "kustomize-upstream")
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.text())
+        .map_err(|err| format!("unable to fetch {}: {}", url, err))?;
+    let release: serde_json::Value = serde_json::from_str(&body).map_err(|err| format!("{}: {}", url, err))?;
+    let tag = release["tag_name"]
+        .as_str()
+        .ok_or_else(|| format!("{} has no tag_name", url))?;
+    if current.starts_with('v') {
+        Ok(tag.to_string())
+    } else {
+        Ok(tag.trim_start_matches('v').to_string())
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{images, manifest};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use yaml_rust::Yaml;
+
+// human readable summary of what a run changed in the generated tree, e.g.
+// for a pull request description
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ChangelogSpec {
+    // path of the markdown file, rendered like the other path templates
+    pub pathTemplate: String,
+}
+
+// resources by kind namespace/name
+type Resources = BTreeMap<String, Yaml>;
+
+// the resources in the documents of yaml files, other documents, e.g.
+// kustomizations without a name, are left out, annotations starting with the
+// provenance prefix are dropped as they change on every run
+pub fn resources<'a>(sources: impl Iterator<Item = &'a str>, provenance_prefix: Option<&str>) -> Resources {
+    let mut resources = Resources::new();
+    for source in sources {
+        for document in manifest::load_manifests(source).into_iter().flatten() {
+            let mut yaml = document.yaml;
+            let (kind, name) = match (yaml["kind"].as_str(), yaml["metadata"]["name"].as_str()) {
+                (Some(kind), Some(name)) => (kind.to_string(), name.to_string()),
+                _ => continue,
+            };
+            let namespace = yaml["metadata"]["namespace"].as_str().unwrap_or_default().to_string();
+            if let Some(prefix) = provenance_prefix {
+                strip_annotations(&mut yaml, prefix);
+            }
+            resources.insert(format!("{} {}/{}", kind, namespace, name), yaml);
+        }
+    }
+    resources
+}
+
+fn strip_annotations(yaml: &mut Yaml, prefix: &str) {
+    let metadata = match yaml {
+        Yaml::Hash(hash) => hash.get_mut(&Yaml::String("metadata".to_string())),
+        _ => None,
+    };
+    let annotations = match metadata {
+        Some(Yaml::Hash(metadata)) => metadata.get_mut(&Yaml::String("annotations".to_string())),
+        _ => None,
+    };
+    if let Some(Yaml::Hash(annotations)) = annotations {
+        let provenance: Vec<Yaml> = annotations
+            .keys()
+            .filter(|key| key.as_str().is_some_and(|key| key.starts_with(prefix)))
+            .cloned()
+            .collect();
+        for key in provenance {
+            annotations.remove(&key);
+        }
+    }
+}
+
+// markdown listing the added, removed and modified resources, the changed
+// images and the permissions roles gained between the previous and the
+// current resources
+pub fn render(title: &str, previous: &Resources, current: &Resources) -> String {
+    let added: Vec<&String> = current.keys().filter(|key| !previous.contains_key(*key)).collect();
+    let removed: Vec<&String> = previous.keys().filter(|key| !current.contains_key(*key)).collect();
+    let modified: Vec<&String> = current
+        .iter()
+        .filter(|(key, yaml)| previous.get(*key).is_some_and(|old| old != *yaml))
+        .map(|(key, _)| key)
+        .collect();
+    let mut changelog = format!("## {}\n", title);
+    section(&mut changelog, "Added resources", added.iter().map(|key| key.to_string()));
+    section(&mut changelog, "Removed resources", removed.iter().map(|key| key.to_string()));
+    section(&mut changelog, "Modified resources", modified.iter().map(|key| key.to_string()));
+    section(&mut changelog, "Images", image_changes(previous, current));
+    section(&mut changelog, "RBAC permissions broadened", broadened_roles(previous, current));
+    if added.is_empty() && removed.is_empty() && modified.is_empty() {
+        changelog.push_str("\nNo resources changed.\n");
+    }
+    changelog
+}
+
+fn section(changelog: &mut String, heading: &str, items: impl Iterator<Item = String>) {
+    let items: Vec<String> = items.collect();
+    if items.is_empty() {
+        return;
+    }
+    changelog.push_str(&format!("\n### {}\n\n", heading));
+    for item in items {
+        changelog.push_str(&format!("- {}\n", item));
+    }
+}
+
+// images by repository, a repository with other tags or digests than before
+// is listed as old -> new
+fn image_changes(previous: &Resources, current: &Resources) -> impl Iterator<Item = String> {
+    let old = images_by_repository(previous);
+    let new = images_by_repository(current);
+    let repositories: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let mut changes = Vec::new();
+    for repository in repositories {
+        let (old_refs, new_refs) = (old.get(repository), new.get(repository));
+        if old_refs == new_refs {
+            continue;
+        }
+        let join = |refs: Option<&BTreeSet<String>>| match refs {
+            Some(refs) => refs.iter().cloned().collect::<Vec<String>>().join(", "),
+            None => "none".to_string(),
+        };
+        changes.push(format!("{}: {} -> {}", repository, join(old_refs), join(new_refs)));
+    }
+    changes.into_iter()
+}
+
+fn images_by_repository(resources: &Resources) -> BTreeMap<String, BTreeSet<String>> {
+    let mut repositories: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for yaml in resources.values() {
+        for image in images::find_images(yaml) {
+            let (repository, reference) = split_image(&image);
+            repositories.entry(repository).or_default().insert(reference);
+        }
+    }
+    repositories
+}
+
+// splits an image into its repository and its tag or digest, a colon in the
+// registry host is not a tag separator
+fn split_image(image: &str) -> (String, String) {
+    if let Some((repository, digest)) = image.split_once('@') {
+        return (repository.to_string(), digest.to_string());
+    }
+    let name_start = image.rfind('/').map_or(0, |idx| idx + 1);
+    match image[name_start..].rfind(':') {
+        Some(idx) => (image[..name_start + idx].to_string(), image[name_start + idx + 1..].to_string()),
+        None => (image.to_string(), "latest".to_string()),
+    }
+}
+
+// roles and cluster roles which exist before and after and gained
+// permissions, listed with the verbs and resources they gained
+fn broadened_roles(previous: &Resources, current: &Resources) -> impl Iterator<Item = String> {
+    let mut broadened = Vec::new();
+    for (key, yaml) in current {
+        if !matches!(yaml["kind"].as_str(), Some("Role") | Some("ClusterRole")) {
+            continue;
+        }
+        let old = match previous.get(key) {
+            Some(old) => permissions(old),
+            None => continue,
+        };
+        let gained: Vec<String> = permissions(yaml)
+            .into_iter()
+            .filter(|permission| !old.contains(permission))
+            .map(|(group, resource, verb)| match group.as_str() {
+                "" => format!("{} {}", verb, resource),
+                group => format!("{} {}.{}", verb, resource, group),
+            })
+            .collect();
+        if !gained.is_empty() {
+            broadened.push(format!("{} gains {}", key, gained.join(", ")));
+        }
+    }
+    broadened.into_iter()
+}
+
+// api group, resource and verb of every permission granted by the rules of
+// a role, non-resource urls are taken as resources
+fn permissions(role: &Yaml) -> BTreeSet<(String, String, String)> {
+    let strings = |yaml: &Yaml| -> Vec<String> {
+        yaml.as_vec()
+            .map(|items| items.iter().filter_map(|item| item.as_str().map(String::from)).collect())
+            .unwrap_or_default()
+    };
+    let mut permissions = BTreeSet::new();
+    for rule in role["rules"].as_vec().map(Vec::as_slice).unwrap_or_default() {
+        let mut groups = strings(&rule["apiGroups"]);
+        if groups.is_empty() {
+            groups.push(String::new());
+        }
+        let mut resources = strings(&rule["resources"]);
+        resources.extend(strings(&rule["nonResourceURLs"]));
+        for group in &groups {
+            for resource in &resources {
+                for verb in strings(&rule["verbs"]) {
+                    permissions.insert((group.clone(), resource.clone(), verb));
+                }
+            }
+        }
+    }
+    permissions
+}
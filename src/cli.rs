@@ -0,0 +1,332 @@
+use crate::logging;
+use kustomize_upstream::{report, Command, Options, Progress};
+use clap::{ArgAction, Args, Parser, Subcommand};
+
+// upstreams fetched at once, also for the commands without --jobs
+const DEFAULT_JOBS: usize = 8;
+
+/// kustomize-upstream reads a multi-document yaml and splits it to multiple
+/// packages each containing one manifest file per manifest using user defined
+/// split rules. Split rules use the kubernetes manifest parameters kind, name
+/// or namespace as criteria. kustomize-upstream generates as well
+/// kustomization.yaml using templates.
+#[derive(Parser)]
+#[command(
+    name = "kustomize-upstream",
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    after_help = "To get started, write a config for an upstream manifest with suggested split rules:\n\n  \
+                  kustomize-upstream init --url https://raw.githubusercontent.com/projectcontour/contour/v1.14.0/examples/render/contour.yaml"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Sub>,
+    // the packages are generated if no subcommand is given
+    #[command(flatten)]
+    config: ConfigArgs,
+    #[command(flatten)]
+    generate: GenerateArgs,
+    #[command(flatten)]
+    log: LogArgs,
+}
+
+#[derive(Subcommand)]
+enum Sub {
+    /// Write the packages of the upstream (the default)
+    Generate {
+        #[command(flatten)]
+        config: ConfigArgs,
+        #[command(flatten)]
+        generate: GenerateArgs,
+    },
+    /// Fail if the generated files on disk are out of date, like generate --check
+    Check {
+        #[command(flatten)]
+        config: ConfigArgs,
+        /// Directory the rendered paths are relative to
+        #[arg(long)]
+        output_dir: Option<String>,
+    },
+    /// Print how the split rules classify the resources
+    Explain {
+        #[command(flatten)]
+        config: ConfigArgs,
+        /// Only explain resources of this kind
+        #[arg(long)]
+        kind: Option<String>,
+        /// Only explain resources of this name
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Print the packages and their resources
+    List {
+        #[command(flatten)]
+        config: ConfigArgs,
+        /// Output format: table, json or yaml
+        #[arg(long = "output", default_value = "table")]
+        format: report::Format,
+    },
+    /// Write a starter config with suggested split rules
+    Init {
+        /// Url of the upstream manifests
+        #[arg(long)]
+        url: String,
+        /// Overwrite an existing config
+        #[arg(long)]
+        force: bool,
+        #[arg(value_name = "CONFIG", default_value = "config.yaml")]
+        config_path: String,
+    },
+    /// Check a config, its rules and templates without fetching the upstream
+    Validate {
+        #[command(flatten)]
+        config: ConfigArgs,
+    },
+    /// Print the JSON Schema of the config
+    Schema,
+    /// Upgrade a config to the current apiVersion
+    Migrate {
+        #[arg(value_name = "CONFIG")]
+        config_path: String,
+    },
+    /// Update Top.version in the config, keeping its formatting
+    Bump {
+        #[command(flatten)]
+        config: ConfigArgs,
+        /// Version to set
+        #[arg(long, required_unless_present = "latest", conflicts_with = "latest")]
+        to: Option<String>,
+        /// Set the latest release of the upstream on GitHub
+        #[arg(long)]
+        latest: bool,
+        /// Generate the packages after bumping the version
+        #[arg(long = "generate")]
+        then_generate: bool,
+        #[command(flatten)]
+        generate: GenerateArgs,
+    },
+    /// Compare the packages of two upstream versions
+    DiffVersions {
+        #[command(flatten)]
+        config: ConfigArgs,
+        /// Version to compare from, Top.version by default
+        #[arg(long)]
+        from: Option<String>,
+        /// Version to compare to
+        #[arg(long)]
+        to: String,
+    },
+    /// Print candidate split rules for an upstream
+    SuggestRules {
+        /// Url of the upstream manifests, instead of the source of a config
+        #[arg(long, conflicts_with = "config_path")]
+        url: Option<String>,
+        #[arg(value_name = "CONFIG", required_unless_present = "url")]
+        config_path: Option<String>,
+    },
+    /// Walk through the classification and add split rules interactively
+    Review {
+        #[command(flatten)]
+        config: ConfigArgs,
+    },
+}
+
+#[derive(Args)]
+struct ConfigArgs {
+    // an Option as the top level one is left unset when a subcommand is given
+    #[arg(value_name = "CONFIG", required = true)]
+    config_path: Option<String>,
+    /// Lay the entry of the Profiles section over the config
+    #[arg(long)]
+    profile: Option<String>,
+    /// Override a config field, e.g. Top.version=1.15.1
+    #[arg(long = "set", value_name = "PATH=VALUE")]
+    sets: Vec<String>,
+}
+
+#[derive(Args, Default)]
+struct GenerateArgs {
+    /// Directory the rendered paths are relative to
+    #[arg(long)]
+    output_dir: Option<String>,
+    /// Delete files of the previous run which are no longer generated
+    #[arg(long)]
+    prune: bool,
+    /// Prune without asking
+    #[arg(long, requires = "prune")]
+    yes: bool,
+    /// Overwrite existing files which were not generated
+    #[arg(long)]
+    force: bool,
+    /// Write a json report of the packages and resources
+    #[arg(long, value_name = "FILE")]
+    report: Option<String>,
+    /// Render everything but leave the file system untouched
+    #[arg(long, group = "mode")]
+    dry_run: bool,
+    /// Print a unified diff of the generated files against the ones on disk
+    #[arg(long, group = "mode")]
+    diff: bool,
+    /// Fail if the generated files on disk are out of date
+    #[arg(long, group = "mode")]
+    check: bool,
+    /// Write the generated files into a .tar.gz or .zip archive
+    #[arg(long, value_name = "FILE", group = "mode")]
+    archive: Option<String>,
+    /// Write the generated files into a snapshot directory, replacing a previous snapshot
+    #[arg(long, value_name = "DIR", group = "mode")]
+    record: Option<String>,
+    /// Fail with a diff if the generated files differ from a recorded snapshot
+    #[arg(long, value_name = "DIR", group = "mode")]
+    verify: Option<String>,
+    /// Print the manifests as one yaml stream
+    #[arg(long, group = "mode")]
+    stdout: bool,
+    /// Print a stable record per file, e.g. CREATED <path>, for scripts
+    #[arg(long, conflicts_with_all = ["stdout", "diff"])]
+    porcelain: bool,
+    /// Write the other resources and packages when one fails, exit with the first failure
+    #[arg(long)]
+    keep_going: bool,
+    /// Fail resources with an apiVersion deprecated for KubernetesVersion instead of warning
+    #[arg(long)]
+    strict_deprecations: bool,
+    /// Fetch up to N upstreams at once
+    #[arg(short, long, value_name = "N", default_value_t = DEFAULT_JOBS)]
+    jobs: usize,
+    /// Only print the manifests of these packages
+    #[arg(long = "package", value_name = "NAME", requires = "stdout")]
+    packages: Vec<String>,
+}
+
+#[derive(Args)]
+struct LogArgs {
+    /// Log how the resources are classified and rendered, -vv for more detail
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Only log errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Log format: text or json
+    #[arg(long, value_name = "FORMAT", default_value = "text", global = true)]
+    log_format: logging::LogFormat,
+}
+
+// parses the command line into the options and the log format, printing
+// help or the error and exiting if it can not be parsed
+pub fn parse() -> (Options, logging::LogFormat) {
+    let cli = Cli::try_parse().unwrap_or_else(|err| {
+        let _ = err.print();
+        std::process::exit(if err.use_stderr() { exitcode::USAGE } else { exitcode::OK });
+    });
+    let mut options = Options {
+        jobs: DEFAULT_JOBS,
+        // -q leaves stderr to the errors
+        progress: match cli.log.quiet {
+            true => Progress::default(),
+            false => Progress::stderr(),
+        },
+        verbosity: match cli.log.quiet {
+            true => -1,
+            false => cli.log.verbose.min(2) as i8,
+        },
+        ..Options::default()
+    };
+    match cli.command {
+        None => {
+            set_config(&mut options, cli.config);
+            set_generate(&mut options, cli.generate);
+        }
+        Some(Sub::Generate { config, generate }) => {
+            set_config(&mut options, config);
+            set_generate(&mut options, generate);
+        }
+        Some(Sub::Check { config, output_dir }) => {
+            set_config(&mut options, config);
+            options.output_dir = output_dir;
+            options.check = true;
+        }
+        Some(Sub::Explain { config, kind, name }) => {
+            options.command = Command::Explain;
+            set_config(&mut options, config);
+            options.kind = kind;
+            options.name = name;
+        }
+        Some(Sub::List { config, format }) => {
+            options.command = Command::List;
+            set_config(&mut options, config);
+            options.format = format;
+        }
+        Some(Sub::Init { url, force, config_path }) => {
+            options.command = Command::Init;
+            options.url = Some(url);
+            options.force = force;
+            options.config_path = config_path;
+        }
+        Some(Sub::Validate { config }) => {
+            options.command = Command::Validate;
+            set_config(&mut options, config);
+        }
+        Some(Sub::Schema) => options.command = Command::Schema,
+        Some(Sub::Migrate { config_path }) => {
+            options.command = Command::Migrate;
+            options.config_path = config_path;
+        }
+        Some(Sub::Bump {
+            config,
+            to,
+            then_generate,
+            generate,
+            ..
+        }) => {
+            options.command = Command::Bump;
+            set_config(&mut options, config);
+            options.to = to;
+            options.generate = then_generate;
+            set_generate(&mut options, generate);
+        }
+        Some(Sub::DiffVersions { config, from, to }) => {
+            options.command = Command::DiffVersions;
+            set_config(&mut options, config);
+            options.from = from;
+            options.to = Some(to);
+        }
+        Some(Sub::SuggestRules { url, config_path }) => {
+            options.command = Command::SuggestRules;
+            options.url = url;
+            options.config_path = config_path.unwrap_or_default();
+        }
+        Some(Sub::Review { config }) => {
+            options.command = Command::Review;
+            set_config(&mut options, config);
+        }
+    }
+    (options, cli.log.log_format)
+}
+
+fn set_config(options: &mut Options, config: ConfigArgs) {
+    options.config_path = config.config_path.unwrap_or_default();
+    options.profile = config.profile;
+    options.sets = config.sets;
+}
+
+fn set_generate(options: &mut Options, generate: GenerateArgs) {
+    options.output_dir = generate.output_dir;
+    options.prune = generate.prune;
+    options.yes = generate.yes;
+    options.force = generate.force;
+    options.report = generate.report;
+    options.dry_run = generate.dry_run;
+    options.diff = generate.diff;
+    options.check = generate.check;
+    options.archive = generate.archive;
+    options.record = generate.record;
+    options.verify = generate.verify;
+    options.stdout = generate.stdout;
+    options.packages = generate.packages;
+    options.porcelain = generate.porcelain;
+    options.keep_going = generate.keep_going;
+    options.strict_deprecations = generate.strict_deprecations;
+    options.jobs = generate.jobs;
+}
@@ -0,0 +1,327 @@
+use crate::manifest;
+use yaml_rust::yaml::Hash;
+use yaml_rust::Yaml;
+
+// apiVersions the kubernetes api server deprecated and later stopped
+// serving, after the kubernetes deprecated API migration guide
+pub struct Deprecation {
+    pub api_version: &'static str,
+    // kinds served by the deprecated apiVersion, all if empty
+    kinds: &'static [&'static str],
+    pub deprecated: Version,
+    pub removed: Version,
+    // apiVersion serving the kinds instead, None if they are gone
+    pub replacement: Option<&'static str>,
+}
+
+// major and minor kubernetes version
+pub type Version = (u32, u32);
+
+const WORKLOADS: &[&str] = &["DaemonSet", "Deployment", "ReplicaSet", "StatefulSet"];
+const RBAC: &[&str] = &["ClusterRole", "ClusterRoleBinding", "Role", "RoleBinding"];
+const FLOW_CONTROL: &[&str] = &["FlowSchema", "PriorityLevelConfiguration"];
+const EXTENSIONS_WORKLOADS: &[&str] = &["DaemonSet", "Deployment", "ReplicaSet"];
+
+// what the api server stores for an unlimited int32 field
+const MAX_INT32: i64 = i32::MAX as i64;
+
+#[rustfmt::skip]
+const DEPRECATIONS: &[Deprecation] = &[
+    Deprecation { api_version: "extensions/v1beta1", kinds: EXTENSIONS_WORKLOADS, deprecated: (1, 9), removed: (1, 16), replacement: Some("apps/v1") },
+    Deprecation { api_version: "extensions/v1beta1", kinds: &["NetworkPolicy"], deprecated: (1, 9), removed: (1, 16), replacement: Some("networking.k8s.io/v1") },
+    Deprecation { api_version: "extensions/v1beta1", kinds: &["PodSecurityPolicy"], deprecated: (1, 10), removed: (1, 16), replacement: Some("policy/v1beta1") },
+    Deprecation { api_version: "extensions/v1beta1", kinds: &["Ingress"], deprecated: (1, 14), removed: (1, 22), replacement: Some("networking.k8s.io/v1") },
+    Deprecation { api_version: "apps/v1beta1", kinds: WORKLOADS, deprecated: (1, 9), removed: (1, 16), replacement: Some("apps/v1") },
+    Deprecation { api_version: "apps/v1beta2", kinds: WORKLOADS, deprecated: (1, 9), removed: (1, 16), replacement: Some("apps/v1") },
+    Deprecation { api_version: "networking.k8s.io/v1beta1", kinds: &["Ingress", "IngressClass"], deprecated: (1, 19), removed: (1, 22), replacement: Some("networking.k8s.io/v1") },
+    Deprecation { api_version: "apiextensions.k8s.io/v1beta1", kinds: &["CustomResourceDefinition"], deprecated: (1, 16), removed: (1, 22), replacement: Some("apiextensions.k8s.io/v1") },
+    Deprecation { api_version: "admissionregistration.k8s.io/v1beta1", kinds: &["MutatingWebhookConfiguration", "ValidatingWebhookConfiguration"], deprecated: (1, 16), removed: (1, 22), replacement: Some("admissionregistration.k8s.io/v1") },
+    Deprecation { api_version: "apiregistration.k8s.io/v1beta1", kinds: &["APIService"], deprecated: (1, 19), removed: (1, 22), replacement: Some("apiregistration.k8s.io/v1") },
+    Deprecation { api_version: "authentication.k8s.io/v1beta1", kinds: &["TokenReview"], deprecated: (1, 19), removed: (1, 22), replacement: Some("authentication.k8s.io/v1") },
+    Deprecation { api_version: "authorization.k8s.io/v1beta1", kinds: &["LocalSubjectAccessReview", "SelfSubjectAccessReview", "SubjectAccessReview"], deprecated: (1, 19), removed: (1, 22), replacement: Some("authorization.k8s.io/v1") },
+    Deprecation { api_version: "certificates.k8s.io/v1beta1", kinds: &["CertificateSigningRequest"], deprecated: (1, 19), removed: (1, 22), replacement: Some("certificates.k8s.io/v1") },
+    Deprecation { api_version: "coordination.k8s.io/v1beta1", kinds: &["Lease"], deprecated: (1, 19), removed: (1, 22), replacement: Some("coordination.k8s.io/v1") },
+    Deprecation { api_version: "rbac.authorization.k8s.io/v1alpha1", kinds: RBAC, deprecated: (1, 17), removed: (1, 22), replacement: Some("rbac.authorization.k8s.io/v1") },
+    Deprecation { api_version: "rbac.authorization.k8s.io/v1beta1", kinds: RBAC, deprecated: (1, 17), removed: (1, 22), replacement: Some("rbac.authorization.k8s.io/v1") },
+    Deprecation { api_version: "scheduling.k8s.io/v1beta1", kinds: &["PriorityClass"], deprecated: (1, 14), removed: (1, 22), replacement: Some("scheduling.k8s.io/v1") },
+    Deprecation { api_version: "storage.k8s.io/v1beta1", kinds: &["CSIDriver", "CSINode", "StorageClass", "VolumeAttachment"], deprecated: (1, 19), removed: (1, 22), replacement: Some("storage.k8s.io/v1") },
+    Deprecation { api_version: "batch/v1beta1", kinds: &["CronJob"], deprecated: (1, 21), removed: (1, 25), replacement: Some("batch/v1") },
+    Deprecation { api_version: "discovery.k8s.io/v1beta1", kinds: &["EndpointSlice"], deprecated: (1, 21), removed: (1, 25), replacement: Some("discovery.k8s.io/v1") },
+    Deprecation { api_version: "events.k8s.io/v1beta1", kinds: &["Event"], deprecated: (1, 19), removed: (1, 25), replacement: Some("events.k8s.io/v1") },
+    Deprecation { api_version: "autoscaling/v2beta1", kinds: &["HorizontalPodAutoscaler"], deprecated: (1, 22), removed: (1, 25), replacement: Some("autoscaling/v2") },
+    Deprecation { api_version: "policy/v1beta1", kinds: &["PodDisruptionBudget"], deprecated: (1, 21), removed: (1, 25), replacement: Some("policy/v1") },
+    Deprecation { api_version: "policy/v1beta1", kinds: &["PodSecurityPolicy"], deprecated: (1, 21), removed: (1, 25), replacement: None },
+    Deprecation { api_version: "node.k8s.io/v1beta1", kinds: &["RuntimeClass"], deprecated: (1, 20), removed: (1, 25), replacement: Some("node.k8s.io/v1") },
+    Deprecation { api_version: "autoscaling/v2beta2", kinds: &["HorizontalPodAutoscaler"], deprecated: (1, 23), removed: (1, 26), replacement: Some("autoscaling/v2") },
+    Deprecation { api_version: "flowcontrol.apiserver.k8s.io/v1beta1", kinds: FLOW_CONTROL, deprecated: (1, 23), removed: (1, 26), replacement: Some("flowcontrol.apiserver.k8s.io/v1") },
+    Deprecation { api_version: "storage.k8s.io/v1beta1", kinds: &["CSIStorageCapacity"], deprecated: (1, 24), removed: (1, 27), replacement: Some("storage.k8s.io/v1") },
+    Deprecation { api_version: "flowcontrol.apiserver.k8s.io/v1beta2", kinds: FLOW_CONTROL, deprecated: (1, 26), removed: (1, 29), replacement: Some("flowcontrol.apiserver.k8s.io/v1") },
+    Deprecation { api_version: "flowcontrol.apiserver.k8s.io/v1beta3", kinds: FLOW_CONTROL, deprecated: (1, 29), removed: (1, 32), replacement: Some("flowcontrol.apiserver.k8s.io/v1") },
+];
+
+// the deprecation of the apiVersion of a kind which applies to a kubernetes
+// version, the newest one if None
+pub fn find(api_version: &str, kind: &str, target: Option<Version>) -> Option<&'static Deprecation> {
+    DEPRECATIONS.iter().find(|deprecation| {
+        deprecation.api_version == api_version
+            && (deprecation.kinds.is_empty() || deprecation.kinds.contains(&kind))
+            && target.is_none_or(|target| target >= deprecation.deprecated)
+    })
+}
+
+// the major and minor version of e.g. 1.29, v1.29.3 or 1.29.0-rc.1
+pub fn parse_version(version: &str) -> Option<Version> {
+    let mut parts = version.trim_start_matches('v').split(['.', '-']);
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+impl Deprecation {
+    // e.g. policy/v1beta1 PodDisruptionBudget is deprecated since 1.21 and
+    // removed in 1.25, use policy/v1
+    pub fn describe(&self, kind: &str, target: Option<Version>) -> String {
+        let state = match target {
+            Some(target) if target >= self.removed => format!("was removed in {}", format_version(self.removed)),
+            _ => format!(
+                "is deprecated since {} and removed in {}",
+                format_version(self.deprecated),
+                format_version(self.removed)
+            ),
+        };
+        match self.replacement {
+            Some(replacement) => format!("{} {} {}, use {}", self.api_version, kind, state, replacement),
+            None => format!("{} {} {} without a replacement", self.api_version, kind, state),
+        }
+    }
+}
+
+fn format_version((major, minor): Version) -> String {
+    format!("{}.{}", major, minor)
+}
+
+// mechanical conversion of a deprecated apiVersion to its replacement
+struct Upgrade {
+    api_version: &'static str,
+    kinds: &'static [&'static str],
+    to: &'static str,
+    // kubernetes version which serves the replacement
+    served: Version,
+    // adapts the fields to the replacement, false if the manifest can not be
+    // converted without changing what it does
+    convert: fn(&mut Yaml) -> bool,
+}
+
+#[rustfmt::skip]
+const UPGRADES: &[Upgrade] = &[
+    Upgrade { api_version: "extensions/v1beta1", kinds: EXTENSIONS_WORKLOADS, to: "apps/v1", served: (1, 9), convert: workload },
+    Upgrade { api_version: "apps/v1beta1", kinds: WORKLOADS, to: "apps/v1", served: (1, 9), convert: workload },
+    Upgrade { api_version: "apps/v1beta2", kinds: WORKLOADS, to: "apps/v1", served: (1, 9), convert: workload },
+    Upgrade { api_version: "extensions/v1beta1", kinds: &["NetworkPolicy"], to: "networking.k8s.io/v1", served: (1, 7), convert: unchanged },
+    Upgrade { api_version: "extensions/v1beta1", kinds: &["Ingress"], to: "networking.k8s.io/v1", served: (1, 19), convert: ingress },
+    Upgrade { api_version: "networking.k8s.io/v1beta1", kinds: &["Ingress"], to: "networking.k8s.io/v1", served: (1, 19), convert: ingress },
+    Upgrade { api_version: "networking.k8s.io/v1beta1", kinds: &["IngressClass"], to: "networking.k8s.io/v1", served: (1, 19), convert: unchanged },
+    Upgrade { api_version: "rbac.authorization.k8s.io/v1beta1", kinds: RBAC, to: "rbac.authorization.k8s.io/v1", served: (1, 8), convert: unchanged },
+    Upgrade { api_version: "scheduling.k8s.io/v1beta1", kinds: &["PriorityClass"], to: "scheduling.k8s.io/v1", served: (1, 14), convert: unchanged },
+    Upgrade { api_version: "coordination.k8s.io/v1beta1", kinds: &["Lease"], to: "coordination.k8s.io/v1", served: (1, 14), convert: unchanged },
+    Upgrade { api_version: "storage.k8s.io/v1beta1", kinds: &["StorageClass", "VolumeAttachment"], to: "storage.k8s.io/v1", served: (1, 13), convert: unchanged },
+    Upgrade { api_version: "storage.k8s.io/v1beta1", kinds: &["CSINode"], to: "storage.k8s.io/v1", served: (1, 17), convert: unchanged },
+    Upgrade { api_version: "storage.k8s.io/v1beta1", kinds: &["CSIDriver"], to: "storage.k8s.io/v1", served: (1, 18), convert: unchanged },
+    Upgrade { api_version: "storage.k8s.io/v1beta1", kinds: &["CSIStorageCapacity"], to: "storage.k8s.io/v1", served: (1, 24), convert: unchanged },
+    Upgrade { api_version: "node.k8s.io/v1beta1", kinds: &["RuntimeClass"], to: "node.k8s.io/v1", served: (1, 20), convert: unchanged },
+    Upgrade { api_version: "batch/v1beta1", kinds: &["CronJob"], to: "batch/v1", served: (1, 21), convert: unchanged },
+    Upgrade { api_version: "policy/v1beta1", kinds: &["PodDisruptionBudget"], to: "policy/v1", served: (1, 21), convert: disruption_budget },
+    Upgrade { api_version: "autoscaling/v2beta2", kinds: &["HorizontalPodAutoscaler"], to: "autoscaling/v2", served: (1, 23), convert: unchanged },
+    Upgrade { api_version: "flowcontrol.apiserver.k8s.io/v1beta1", kinds: FLOW_CONTROL, to: "flowcontrol.apiserver.k8s.io/v1", served: (1, 29), convert: flow_control },
+    Upgrade { api_version: "flowcontrol.apiserver.k8s.io/v1beta2", kinds: FLOW_CONTROL, to: "flowcontrol.apiserver.k8s.io/v1", served: (1, 29), convert: flow_control },
+    Upgrade { api_version: "flowcontrol.apiserver.k8s.io/v1beta3", kinds: FLOW_CONTROL, to: "flowcontrol.apiserver.k8s.io/v1", served: (1, 29), convert: unchanged },
+];
+
+// rewrites a manifest with an apiVersion the kubernetes version deprecated,
+// the newest one if None, to the replacement if it is served and the
+// conversion is mechanical, returns the previous apiVersion
+pub fn upgrade(manifest: &mut Yaml, target: Option<Version>) -> Option<String> {
+    let api_version = manifest["apiVersion"].as_str()?.to_string();
+    let kind = manifest["kind"].as_str()?.to_string();
+    find(&api_version, &kind, target)?;
+    let upgrade = UPGRADES.iter().find(|upgrade| {
+        upgrade.api_version == api_version
+            && upgrade.kinds.contains(&kind.as_str())
+            && target.is_none_or(|target| target >= upgrade.served)
+    })?;
+    let mut upgraded = manifest.clone();
+    if !(upgrade.convert)(&mut upgraded) {
+        return None;
+    }
+    if let Yaml::Hash(hash) = &mut upgraded {
+        manifest::set_entry(hash, key("apiVersion"), Yaml::String(upgrade.to.to_string()));
+    }
+    *manifest = upgraded;
+    Some(api_version)
+}
+
+fn unchanged(_manifest: &mut Yaml) -> bool {
+    true
+}
+
+// apps/v1 requires the selector the older versions defaulted to the labels
+// of the pod template, has no rollback fields and defaults some fields
+// differently, the old defaults are written where the fields are unset
+fn workload(manifest: &mut Yaml) -> bool {
+    let api_version = manifest["apiVersion"].as_str().unwrap_or_default().to_string();
+    let kind = manifest["kind"].as_str().unwrap_or_default().to_string();
+    let labels = manifest["spec"]["template"]["metadata"]["labels"].clone();
+    let spec = match manifest::field_mut(manifest, &["spec"]) {
+        Some(Yaml::Hash(spec)) => spec,
+        _ => return false,
+    };
+    if spec.get(&key("selector")).is_none_or(|selector| selector.is_null()) {
+        if labels.as_hash().is_none_or(|labels| labels.is_empty()) {
+            return false;
+        }
+        let mut selector = Hash::new();
+        selector.insert(key("matchLabels"), labels);
+        manifest::set_entry(spec, key("selector"), Yaml::Hash(selector));
+    }
+    spec.remove(&key("rollbackTo"));
+    spec.remove(&key("templateGeneration"));
+    match (api_version.as_str(), kind.as_str()) {
+        ("extensions/v1beta1", "Deployment") => {
+            // no limit, which apps/v1 defaults to 10 revisions and 600s
+            set_default(spec, "revisionHistoryLimit", Yaml::Integer(MAX_INT32));
+            set_default(spec, "progressDeadlineSeconds", Yaml::Integer(MAX_INT32));
+            let strategy = match entry_hash(spec, "strategy") {
+                Some(strategy) => strategy,
+                None => return false,
+            };
+            set_default(strategy, "type", key("RollingUpdate"));
+            if strategy.get(&key("type")).and_then(Yaml::as_str) == Some("RollingUpdate") {
+                // apps/v1 defaults to 25% each
+                let rolling_update = match entry_hash(strategy, "rollingUpdate") {
+                    Some(rolling_update) => rolling_update,
+                    None => return false,
+                };
+                set_default(rolling_update, "maxSurge", Yaml::Integer(1));
+                set_default(rolling_update, "maxUnavailable", Yaml::Integer(1));
+            }
+        }
+        ("apps/v1beta1", "Deployment") => set_default(spec, "revisionHistoryLimit", Yaml::Integer(2)),
+        ("extensions/v1beta1", "DaemonSet") | ("apps/v1beta1", "StatefulSet") => {
+            // apps/v1 updates the pods on every change of the template
+            let update_strategy = match entry_hash(spec, "updateStrategy") {
+                Some(update_strategy) => update_strategy,
+                None => return false,
+            };
+            set_default(update_strategy, "type", key("OnDelete"));
+        }
+        _ => {}
+    }
+    true
+}
+
+// the mapping at a key, inserted if the key is unset, None if it holds
+// anything else
+fn entry_hash<'a>(hash: &'a mut Hash, name: &str) -> Option<&'a mut Hash> {
+    let entry = hash.entry(key(name)).or_insert(Yaml::Null);
+    if entry.is_null() {
+        *entry = Yaml::Hash(Hash::new());
+    }
+    match entry {
+        Yaml::Hash(entry) => Some(entry),
+        _ => None,
+    }
+}
+
+// sets a field unless it is set
+fn set_default(hash: &mut Hash, name: &str, value: Yaml) {
+    if hash.get(&key(name)).is_none_or(Yaml::is_null) {
+        manifest::set_entry(hash, key(name), value);
+    }
+}
+
+// networking.k8s.io/v1 renames the default backend, nests the service of
+// the backends and requires a pathType
+fn ingress(manifest: &mut Yaml) -> bool {
+    let spec = match manifest::field_mut(manifest, &["spec"]) {
+        Some(Yaml::Hash(spec)) => spec,
+        _ => return true,
+    };
+    if let Some(mut backend) = spec.remove(&key("backend")) {
+        if !service_backend(&mut backend) {
+            return false;
+        }
+        spec.insert(key("defaultBackend"), backend);
+    }
+    if let Some(Yaml::Array(rules)) = spec.get_mut(&key("rules")) {
+        for rule in rules.iter_mut() {
+            if let Some(Yaml::Array(paths)) = manifest::field_mut(rule, &["http", "paths"]) {
+                for path in paths.iter_mut() {
+                    if let Yaml::Hash(path) = path {
+                        if path.get(&key("pathType")).is_none() {
+                            path.insert(key("pathType"), Yaml::String("ImplementationSpecific".to_string()));
+                        }
+                        if let Some(backend) = path.get_mut(&key("backend")) {
+                            if !service_backend(backend) {
+                                return false;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    true
+}
+
+// serviceName and servicePort become service.name and service.port.number,
+// or service.port.name for a named port, resource backends stay as they are
+fn service_backend(backend: &mut Yaml) -> bool {
+    let backend = match backend {
+        Yaml::Hash(backend) => backend,
+        _ => return false,
+    };
+    let (name, port) = match (backend.remove(&key("serviceName")), backend.remove(&key("servicePort"))) {
+        (None, None) => return true,
+        (Some(name), Some(port)) => (name, port),
+        _ => return false,
+    };
+    let port_field = match port {
+        Yaml::Integer(_) => "number",
+        Yaml::String(_) => "name",
+        _ => return false,
+    };
+    let mut service_port = Hash::new();
+    service_port.insert(key(port_field), port);
+    let mut service = Hash::new();
+    service.insert(key("name"), name);
+    service.insert(key("port"), Yaml::Hash(service_port));
+    backend.insert(key("service"), Yaml::Hash(service));
+    true
+}
+
+// an empty selector matches no pods in policy/v1beta1 but every pod of the
+// namespace in policy/v1
+fn disruption_budget(manifest: &mut Yaml) -> bool {
+    let selector = &manifest["spec"]["selector"];
+    ["matchLabels", "matchExpressions"]
+        .iter()
+        .any(|field| match &selector[*field] {
+            Yaml::Hash(entries) => !entries.is_empty(),
+            Yaml::Array(expressions) => !expressions.is_empty(),
+            _ => false,
+        })
+}
+
+// v1 calls the concurrency shares of a priority level nominal
+fn flow_control(manifest: &mut Yaml) -> bool {
+    if let Some(Yaml::Hash(limited)) = manifest::field_mut(manifest, &["spec", "limited"]) {
+        if let Some(shares) = limited.remove(&key("assuredConcurrencyShares")) {
+            limited.insert(key("nominalConcurrencyShares"), shares);
+        }
+    }
+    true
+}
+
+fn key(name: &str) -> Yaml {
+    Yaml::String(name.to_string())
+}
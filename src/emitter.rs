@@ -0,0 +1,495 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use yaml_rust::parser::{Event, EventReceiver, Parser};
+use yaml_rust::scanner::TScalarStyle;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlLoader};
+
+// width of the lines of folded block scalars without lineWidth
+const FOLD_WIDTH: usize = 80;
+
+// how manifests are written, the defaults match the output of the
+// yaml-rust emitter used before
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct EmitterSpec {
+    // write the upstream text of manifests which were not modified, keeping
+    // comments, blank lines and quoting
+    preserveSource: bool,
+    // spaces per nesting level
+    indent: usize,
+    // indent sequences inside mappings, `key:\n  - item` instead of `key:\n- item`
+    indentSequences: bool,
+    // start every manifest with `---`
+    documentMarker: bool,
+    // single line strings longer than this are written as folded block
+    // scalars, unset never wraps
+    lineWidth: Option<usize>,
+    quoteStyle: QuoteStyle,
+    // write multi-line strings, e.g. certificates and scripts, as literal
+    // block scalars instead of double quoted strings with escaped newlines
+    literalBlocks: bool,
+    // write strings which are literal or folded block scalars in the upstream
+    // text in the same style
+    preserveBlockScalars: bool,
+}
+
+impl Default for EmitterSpec {
+    fn default() -> Self {
+        EmitterSpec {
+            preserveSource: false,
+            indent: 2,
+            indentSequences: true,
+            documentMarker: true,
+            lineWidth: None,
+            quoteStyle: QuoteStyle::Minimal,
+            literalBlocks: false,
+            preserveBlockScalars: false,
+        }
+    }
+}
+
+// quoting of string values, keys are only quoted when required
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+enum QuoteStyle {
+    // quote only strings which would not read back as the same string
+    Minimal,
+    Double,
+    Single,
+}
+
+// returns the yaml of a manifest, source is the upstream text it was parsed from
+pub fn emit(spec: &EmitterSpec, manifest: &Yaml, source: Option<&str>) -> String {
+    if let (true, Some(source)) = (spec.preserveSource, source) {
+        // the text is only reused if it still parses to the written manifest
+        let original = YamlLoader::load_from_str(source)
+            .ok()
+            .and_then(|documents| documents.into_iter().next());
+        if original.as_ref() == Some(manifest) {
+            return document(spec, source.trim_end());
+        }
+    }
+
+    let block_styles = match (spec.preserveBlockScalars, source) {
+        (true, Some(source)) => block_styles(source),
+        _ => HashMap::new(),
+    };
+    let mut out_str = String::new();
+    Emitter {
+        spec,
+        block_styles: &block_styles,
+        out: &mut out_str,
+    }
+    .emit_top(manifest);
+    document(spec, out_str.trim_start_matches('\n'))
+}
+
+// the block scalar indicator of the strings written as block scalars in
+// the source text, the manifest no longer tells where a string came from so
+// they are found by their value
+fn block_styles(source: &str) -> HashMap<String, char> {
+    struct Styles(HashMap<String, char>);
+    impl EventReceiver for Styles {
+        fn on_event(&mut self, event: Event) {
+            match event {
+                Event::Scalar(value, TScalarStyle::Literal, _, _) => {
+                    self.0.insert(value, '|');
+                }
+                Event::Scalar(value, TScalarStyle::Foled, _, _) => {
+                    self.0.insert(value, '>');
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut styles = Styles(HashMap::new());
+    // an unparsable source keeps the styles found before the error
+    let _ = Parser::new(source.chars()).load(&mut styles, false);
+    styles.0
+}
+
+// every written manifest ends with a newline
+fn document(spec: &EmitterSpec, content: &str) -> String {
+    if spec.documentMarker {
+        format!("---\n{}\n", content)
+    } else {
+        format!("{}\n", content)
+    }
+}
+
+struct Emitter<'a> {
+    spec: &'a EmitterSpec,
+    block_styles: &'a HashMap<String, char>,
+    out: &'a mut String,
+}
+
+impl<'a> Emitter<'a> {
+    fn emit_top(&mut self, node: &Yaml) {
+        match node {
+            Yaml::Hash(hash) if !hash.is_empty() => self.emit_hash(hash, 0, false),
+            Yaml::Array(array) if !array.is_empty() => self.emit_array(array, 0, false),
+            _ => {
+                // the lines of a top level block scalar start at its indicator
+                self.out.push('\n');
+                self.emit_scalar(node, self.spec.indent.max(1), 0, false);
+            }
+        }
+    }
+
+    // every entry starts on a new line at column col, except the first one
+    // if it follows a `- ` on the current line
+    fn emit_hash(&mut self, hash: &Hash, col: usize, inline: bool) {
+        for (idx, (key, value)) in hash.iter().enumerate() {
+            if idx > 0 || !inline {
+                self.new_line(col);
+            }
+            match key {
+                Yaml::Hash(_) | Yaml::Array(_) => {
+                    self.out.push('?');
+                    self.emit_item(key, col);
+                    self.new_line(col);
+                    self.out.push(':');
+                    self.emit_item(value, col);
+                }
+                _ => {
+                    self.emit_scalar(key, col, col, true);
+                    self.out.push(':');
+                    self.emit_value(value, col);
+                }
+            }
+        }
+    }
+
+    fn emit_array(&mut self, array: &[Yaml], col: usize, inline: bool) {
+        for (idx, item) in array.iter().enumerate() {
+            if idx > 0 || !inline {
+                self.new_line(col);
+            }
+            self.out.push('-');
+            self.emit_item(item, col);
+        }
+    }
+
+    // value of a mapping entry whose key starts at column col
+    fn emit_value(&mut self, value: &Yaml, col: usize) {
+        match value {
+            Yaml::Hash(hash) if !hash.is_empty() => self.emit_hash(hash, col + self.spec.indent, false),
+            Yaml::Array(array) if !array.is_empty() => {
+                let offset = if self.spec.indentSequences { self.spec.indent } else { 0 };
+                self.emit_array(array, col + offset, false)
+            }
+            _ => {
+                self.out.push(' ');
+                self.emit_scalar(value, col + self.spec.indent, col, false);
+            }
+        }
+    }
+
+    // sequence item or complex key after the indicator at column col, nested
+    // collections continue at the column after `- `
+    fn emit_item(&mut self, item: &Yaml, col: usize) {
+        self.out.push(' ');
+        match item {
+            Yaml::Hash(hash) if !hash.is_empty() => self.emit_hash(hash, col + 2, true),
+            Yaml::Array(array) if !array.is_empty() => self.emit_array(array, col + 2, true),
+            _ => self.emit_scalar(item, col + 2, col, false),
+        }
+    }
+
+    // scalar, empty collection or string, the lines of block scalars are
+    // indented to col, parent_col is the column of their key or `- `
+    fn emit_scalar(&mut self, node: &Yaml, col: usize, parent_col: usize, is_key: bool) {
+        match node {
+            Yaml::Hash(_) => self.out.push_str("{}"),
+            Yaml::Array(_) => self.out.push_str("[]"),
+            Yaml::String(value) => self.emit_string(value, col, parent_col, is_key),
+            Yaml::Boolean(value) => self.out.push_str(if *value { "true" } else { "false" }),
+            Yaml::Integer(value) => self.out.push_str(&value.to_string()),
+            Yaml::Real(value) => self.out.push_str(value),
+            _ => self.out.push('~'),
+        }
+    }
+
+    fn emit_string(&mut self, value: &str, col: usize, parent_col: usize, is_key: bool) {
+        if !is_key && is_block_content(value) {
+            let source_style = self.block_styles.get(value).copied();
+            if source_style == Some('>') {
+                let width = self.spec.lineWidth.unwrap_or(FOLD_WIDTH).saturating_sub(col).max(1);
+                if let Some(lines) = fold_lines(self.spec, value, width) {
+                    let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+                    self.emit_block('>', value, &lines, col, parent_col);
+                    return;
+                }
+            }
+            let multi_line = value.trim_end_matches('\n').contains('\n');
+            if source_style.is_some() || (self.spec.literalBlocks && multi_line) {
+                let lines: Vec<&str> = value.trim_end_matches('\n').split('\n').collect();
+                self.emit_block('|', value, &lines, col, parent_col);
+                return;
+            }
+            let folded = match self.spec.lineWidth {
+                Some(width) if col + value.chars().count() > width => {
+                    fold(value.trim_end_matches('\n'), width.saturating_sub(col).max(1))
+                }
+                _ => None,
+            };
+            if let Some(lines) = folded {
+                let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+                self.emit_block('>', value, &lines, col, parent_col);
+                return;
+            }
+        }
+        let style = if is_key { QuoteStyle::Minimal } else { self.spec.quoteStyle };
+        match style {
+            QuoteStyle::Single if !needs_escape(value) => {
+                self.out.push('\'');
+                self.out.push_str(&value.replace('\'', "''"));
+                self.out.push('\'');
+            }
+            QuoteStyle::Minimal if !need_quotes(value) => self.out.push_str(value),
+            _ => {
+                self.out.push('"');
+                self.out.push_str(&escape_double(value));
+                self.out.push('"');
+            }
+        }
+    }
+
+    // block scalar with the lines indented to col, the chomping indicator
+    // restores the trailing newlines of the value
+    fn emit_block(&mut self, style: char, value: &str, lines: &[&str], col: usize, parent_col: usize) {
+        self.out.push(style);
+        // an indented first line would be taken as the block indentation, the
+        // indicator gives it relative to the key or `- ` of the scalar
+        let first_line = lines.iter().find(|line| !line.is_empty());
+        if let Some(true) = first_line.map(|line| line.starts_with(' ')) {
+            self.out.push_str(&(col - parent_col).max(1).to_string());
+        }
+        let trailing_newlines = value.len() - value.trim_end_matches('\n').len();
+        match trailing_newlines {
+            0 => self.out.push('-'),
+            1 => {}
+            _ => self.out.push('+'),
+        }
+        for line in lines {
+            if line.is_empty() {
+                self.out.push('\n');
+            } else {
+                self.new_line(col);
+                self.out.push_str(line);
+            }
+        }
+        for _ in 1..trailing_newlines {
+            self.out.push('\n');
+        }
+    }
+
+    fn new_line(&mut self, col: usize) {
+        self.out.push('\n');
+        self.out.push_str(&" ".repeat(col));
+    }
+}
+
+// true if the string can be written as a block scalar, which can not
+// express control characters other than newlines and tabs
+fn is_block_content(value: &str) -> bool {
+    !value.trim().is_empty() && !value.contains(|c: char| c.is_control() && c != '\n' && c != '\t')
+}
+
+// splits a single line string at single spaces into lines of at most width
+// characters where possible, a line break in a folded block scalar reads
+// back as one space, None if the string can not be folded losslessly
+fn fold(value: &str, width: usize) -> Option<Vec<String>> {
+    if value.contains(|c: char| c.is_control()) || value.contains("  ") {
+        return None;
+    }
+    if value.starts_with(' ') || value.ends_with(' ') || !value.contains(' ') {
+        return None;
+    }
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in value.split(' ') {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        } else if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    Some(lines)
+}
+
+// lines of a folded block scalar reading back as the string, line breaks of
+// the string are written as empty lines and its lines are folded to width,
+// None if the string can not be written as a folded block scalar
+fn fold_lines(spec: &EmitterSpec, value: &str, width: usize) -> Option<Vec<String>> {
+    let mut lines: Vec<String> = Vec::new();
+    for (idx, line) in value.trim_end_matches('\n').split('\n').enumerate() {
+        if idx > 0 {
+            lines.push(String::new());
+        }
+        match fold(line, width) {
+            Some(folded) => lines.extend(folded),
+            None if !line.is_empty() => lines.push(line.to_string()),
+            None => {}
+        }
+    }
+    // more indented lines keep their line breaks, which the empty lines
+    // would double, so the lines are only taken if they read back the same
+    let mut block = String::from("k: ");
+    let block_lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+    Emitter {
+        spec,
+        block_styles: &HashMap::new(),
+        out: &mut block,
+    }
+    .emit_block('>', value, &block_lines, spec.indent.max(1), 0);
+    block.push('\n');
+    let documents = YamlLoader::load_from_str(&block).ok()?;
+    match documents.first().map(|document| &document["k"]) {
+        Some(Yaml::String(read)) if read == value => Some(lines),
+        _ => None,
+    }
+}
+
+fn needs_escape(value: &str) -> bool {
+    value.contains(|c: char| c.is_control())
+}
+
+fn escape_double(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\x08' => escaped.push_str("\\b"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\x0c' => escaped.push_str("\\f"),
+            '\r' => escaped.push_str("\\r"),
+            c if c.is_ascii_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// true if the plain string would not read back as the same string, the
+// same rules the yaml-rust emitter applies
+fn need_quotes(value: &str) -> bool {
+    value.is_empty()
+        || value.starts_with(' ')
+        || value.ends_with(' ')
+        || value.starts_with(|c: char| "&*?|-<>=!%@.".contains(c))
+        || value.contains(|c: char| ":{}[],#`\"'\\".contains(c) || c.is_ascii_control())
+        || [
+            "yes", "Yes", "YES", "no", "No", "NO", "True", "TRUE", "true", "False", "FALSE",
+            "false", "on", "On", "ON", "off", "Off", "OFF", "null", "Null", "NULL", "~",
+        ]
+        .contains(&value)
+        || value.starts_with("0x")
+        || value.parse::<i64>().is_ok()
+        || value.parse::<f64>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // strings which need a block scalar indicator, chomping or escaping
+    const STRINGS: &[&str] = &[
+        "#!/bin/sh\necho hi\n",
+        "  indented first line\nsecond\n",
+        "\n  after an empty line\n",
+        "no trailing newline\nsecond",
+        "trailing newlines\n\n\n",
+        "tab\tand\nnext\n",
+        "  ",
+        "single line with a trailing newline\n",
+    ];
+
+    fn specs() -> Vec<EmitterSpec> {
+        let mut specs = Vec::new();
+        for indent in [2, 4] {
+            for indent_sequences in [true, false] {
+                specs.push(EmitterSpec {
+                    indent,
+                    indentSequences: indent_sequences,
+                    literalBlocks: true,
+                    lineWidth: Some(20),
+                    ..EmitterSpec::default()
+                });
+            }
+        }
+        specs
+    }
+
+    fn assert_round_trip(spec: &EmitterSpec, manifest: &Yaml, source: Option<&str>) {
+        let emitted = emit(spec, manifest, source);
+        let read = YamlLoader::load_from_str(&emitted)
+            .unwrap_or_else(|err| panic!("{:?} emitted unparsable yaml {}:\n{}", spec, err, emitted));
+        assert_eq!(read.first(), Some(manifest), "{:?} emitted\n{}", spec, emitted);
+    }
+
+    fn strings() -> Vec<Yaml> {
+        STRINGS.iter().map(|value| Yaml::String(value.to_string())).collect()
+    }
+
+    #[test]
+    fn block_scalars_in_mappings_round_trip() {
+        let mut data = Hash::new();
+        for (idx, value) in strings().into_iter().enumerate() {
+            data.insert(Yaml::String(format!("key{}", idx)), value);
+        }
+        let mut manifest = Hash::new();
+        manifest.insert(Yaml::String("data".to_string()), Yaml::Hash(data));
+        for spec in specs() {
+            assert_round_trip(&spec, &Yaml::Hash(manifest.clone()), None);
+        }
+    }
+
+    #[test]
+    fn block_scalars_in_sequences_round_trip() {
+        let nested = Yaml::Array(vec![Yaml::Array(strings()), Yaml::Array(strings())]);
+        let mut manifest = Hash::new();
+        manifest.insert(Yaml::String("args".to_string()), Yaml::Array(strings()));
+        manifest.insert(Yaml::String("nested".to_string()), nested);
+        for spec in specs() {
+            assert_round_trip(&spec, &Yaml::Hash(manifest.clone()), None);
+            assert_round_trip(&spec, &Yaml::Array(strings()), None);
+        }
+    }
+
+    #[test]
+    fn top_level_strings_round_trip() {
+        for spec in specs() {
+            for value in strings() {
+                assert_round_trip(&spec, &value, None);
+            }
+        }
+    }
+
+    #[test]
+    fn source_block_styles_are_kept() {
+        let source = "data:\n  script: |\n    echo hi\n  text: >\n    folded\n    line\n\n    paragraph\n  items:\n    - >2-\n        more indented\n      line\n";
+        let manifest = YamlLoader::load_from_str(source).unwrap().remove(0);
+        for indent in [2, 4] {
+            let spec = EmitterSpec {
+                indent,
+                preserveBlockScalars: true,
+                ..EmitterSpec::default()
+            };
+            assert_round_trip(&spec, &manifest, Some(source));
+            let emitted = emit(&spec, &manifest, Some(source));
+            assert!(emitted.contains("script: |"), "{}", emitted);
+            assert!(emitted.contains("text: >"), "{}", emitted);
+        }
+        // without the option multi-line strings are quoted as before
+        let emitted = emit(&EmitterSpec::default(), &manifest, Some(source));
+        assert!(emitted.contains("script: \"echo hi\\n\""), "{}", emitted);
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+// errors which stop a run, each names the config field, document, template
+// or file it is about, the exit code tells scripts which kind of step failed:
+// 78 config, 69 fetch, 65 document, render or drift, 66 read, 73 write,
+// 64 usage
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    // the config can not be read, parsed or is not valid
+    #[error("{0}")]
+    Config(String),
+    #[error("unable to fetch {url}: {reason}")]
+    Fetch { url: String, reason: String },
+    // an upstream document can not be parsed or is no valid resource
+    #[error("{0}")]
+    Document(String),
+    #[error("unable to render {template}: {}", describe(source))]
+    Template { template: String, source: tera::Error },
+    #[error("classify script of SplitRules[{rule_idx}] failed: {reason}")]
+    Script { rule_idx: usize, reason: String },
+    // the rendered paths can not be written, e.g. two resources share a file
+    #[error("{0}")]
+    Layout(String),
+    // the resource or package an error happened for, e.g. a template
+    // rendered for it fails
+    #[error("{context}: {source}")]
+    Context { context: String, source: Box<Error> },
+    #[error("unable to {action} {}: {source}", path.display())]
+    File {
+        action: &'static str,
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("refused to overwrite {} files not generated by a previous run, use --force:\n  {}", .0.len(), paths(.0))]
+    Refused(Vec<PathBuf>),
+    // the command line asks for something the config does not allow
+    #[error("{0}")]
+    Usage(String),
+    // the generated files differ from the ones on disk under --check or
+    // from a snapshot under --verify
+    #[error("{what}:\n  {}", .differences.join("\n  "))]
+    Drift { what: String, differences: Vec<String> },
+    // the resources and packages which failed under --keep-going
+    #[error("{} failures:\n  {}", .0.len(), failures(.0))]
+    Failures(Vec<Error>),
+}
+
+impl Error {
+    pub fn file(action: &'static str, path: impl Into<PathBuf>) -> impl FnOnce(std::io::Error) -> Error {
+        let path = path.into();
+        move |source| Error::File { action, path, source }
+    }
+
+    // adds the resource or package the error happened for
+    pub fn context(context: &str) -> impl FnOnce(Error) -> Error + '_ {
+        move |source| Error::Context {
+            context: context.to_string(),
+            source: Box::new(source),
+        }
+    }
+
+    pub fn exit_code(&self) -> exitcode::ExitCode {
+        match self {
+            Error::Config(_) => exitcode::CONFIG,
+            Error::Fetch { .. } => exitcode::UNAVAILABLE,
+            Error::Document(_)
+            | Error::Template { .. }
+            | Error::Script { .. }
+            | Error::Layout(_)
+            | Error::Drift { .. } => exitcode::DATAERR,
+            Error::Context { source, .. } => source.exit_code(),
+            Error::File { action: "read", .. } => exitcode::NOINPUT,
+            Error::File { .. } | Error::Refused(_) => exitcode::CANTCREAT,
+            Error::Usage(_) => exitcode::USAGE,
+            // the first failure, later ones may only follow from it
+            Error::Failures(failures) => failures.first().map_or(exitcode::SOFTWARE, Error::exit_code),
+        }
+    }
+}
+
+// the message of an error followed by the messages of its sources, tera
+// only tells what went wrong in the source, sources already quoted by the
+// message before them are left out
+pub fn describe(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        let cause = err.to_string();
+        if !message.contains(&cause) {
+            message.push_str(&format!(": {}", cause));
+        }
+        source = err.source();
+    }
+    message
+}
+
+fn paths(paths: &[PathBuf]) -> String {
+    let paths: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+    paths.join("\n  ")
+}
+
+fn failures(failures: &[Error]) -> String {
+    let failures: Vec<String> = failures.iter().map(|err| err.to_string().replace('\n', "\n  ")).collect();
+    failures.join("\n  ")
+}
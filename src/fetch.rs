@@ -0,0 +1,69 @@
+use crate::progress::Step;
+use crate::{error, Error, Progress};
+use futures::stream::{self, StreamExt};
+use std::fs::File;
+use std::io::{Seek, Write};
+use tracing::debug;
+
+// body of a source, spooled to a temporary file to keep large upstreams out
+// of memory, and the charset declared for it
+pub type Download = (File, Option<String>);
+
+// downloads the sources concurrently, at most jobs at once, the downloads
+// are in the order of the sources
+pub fn fetch_all(sources: &[String], jobs: usize, progress: &Progress) -> Vec<Result<Download, Error>> {
+    let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(err) => return sources.iter().map(|source| Err(fetch_error(source, err.to_string()))).collect(),
+    };
+    let client = reqwest::Client::new();
+    let step = progress.bytes("download");
+    runtime.block_on(
+        stream::iter(sources)
+            .map(|source| fetch_source(&client, source, &step))
+            .buffered(jobs.max(1))
+            .collect(),
+    )
+}
+
+// downloads a single source
+pub fn fetch(source: &str) -> Result<Download, Error> {
+    fetch_all(&[source.to_string()], 1, &Progress::default()).remove(0)
+}
+
+async fn fetch_source(client: &reqwest::Client, source: &str, step: &Step) -> Result<Download, Error> {
+    debug!("fetch {}", source);
+    let mut resp = client
+        .get(source)
+        .send()
+        .await
+        .map_err(|err| fetch_error(source, error::describe(&err)))?;
+    if resp.status() != reqwest::StatusCode::OK {
+        return Err(fetch_error(source, resp.status().to_string()));
+    }
+
+    let charset = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(|content_type| content_type.split("charset=").nth(1))
+        .map(|charset| charset.trim_matches('"').to_lowercase());
+    step.inc_length(resp.content_length());
+    let mut body = tempfile::tempfile().map_err(Error::file("create", std::env::temp_dir()))?;
+    let mut size = 0;
+    while let Some(chunk) = resp.chunk().await.map_err(|err| fetch_error(source, error::describe(&err)))? {
+        body.write_all(&chunk).map_err(Error::file("write", std::env::temp_dir()))?;
+        size += chunk.len();
+        step.inc(chunk.len() as u64);
+    }
+    body.rewind().map_err(Error::file("read", std::env::temp_dir()))?;
+    debug!("fetched {} ({} bytes)", source, size);
+    Ok((body, charset))
+}
+
+fn fetch_error(source: &str, reason: String) -> Error {
+    Error::Fetch {
+        url: source.to_string(),
+        reason,
+    }
+}
@@ -0,0 +1,268 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use tera::Tera;
+
+pub fn register(tera: &mut Tera) {
+    tera.register_filter("pad", PadFn { default_width: 3 });
+    tera.register_filter("pad3", PadFn { default_width: 3 });
+    tera.register_filter("slugify", SlugifyFn {});
+    tera.register_filter("kebab", KebabFn {});
+    tera.register_filter("snake", SnakeFn {});
+    tera.register_filter("sha256", Sha256Fn {});
+    tera.register_filter("b64encode", B64EncodeFn {});
+    tera.register_filter("b64decode", B64DecodeFn {});
+    tera.register_filter("sanitize", SanitizeFn {});
+}
+
+// zero pads a number to the width given by the `width` argument,
+// e.g. {{ resource.index | pad(width=5) }}
+pub struct PadFn {
+    default_width: usize,
+}
+
+impl tera::Filter for PadFn {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        let width = match args.get("width") {
+            Some(width) => match width.as_u64() {
+                Some(width) => width as usize,
+                None => return Err("expect number as width".into()),
+            },
+            None => self.default_width,
+        };
+        match value {
+            tera::Value::Number(num) => {
+                if let Some(num) = num.as_u64() {
+                    let result = format!("{:0width$}", num, width = width);
+                    Ok(tera::Value::String(result))
+                } else {
+                    Err("expect number".into())
+                }
+            }
+            _ => Err("expect number".into()),
+        }
+    }
+}
+
+// lowercases and replaces every run of non alphanumeric characters by a
+// single dash, e.g. "httpproxies.projectcontour.io" -> "httpproxies-projectcontour-io"
+pub struct SlugifyFn {}
+
+impl tera::Filter for SlugifyFn {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        _args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        let value = expect_str(value)?;
+        Ok(tera::Value::String(slugify(value)))
+    }
+}
+
+// like slugify but additionally splits camel case words,
+// e.g. "ClusterRoleBinding" -> "cluster-role-binding"
+pub struct KebabFn {}
+
+impl tera::Filter for KebabFn {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        _args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        let value = expect_str(value)?;
+        Ok(tera::Value::String(split_words(value, true).join("-")))
+    }
+}
+
+// like kebab but joins the words with underscores,
+// e.g. "ClusterRoleBinding" -> "cluster_role_binding"
+pub struct SnakeFn {}
+
+impl tera::Filter for SnakeFn {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        _args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        let value = expect_str(value)?;
+        Ok(tera::Value::String(split_words(value, true).join("_")))
+    }
+}
+
+// hex encoded sha256 digest of a string, other values are hashed in their
+// json representation, e.g. {{ resource | sha256 | truncate(length=8, end="") }}
+pub struct Sha256Fn {}
+
+impl tera::Filter for Sha256Fn {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        _args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        let digest = match value {
+            tera::Value::String(value) => Sha256::digest(value.as_bytes()),
+            _ => Sha256::digest(value.to_string().as_bytes()),
+        };
+        Ok(tera::Value::String(format!("{:x}", digest)))
+    }
+}
+
+pub struct B64EncodeFn {}
+
+impl tera::Filter for B64EncodeFn {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        _args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        let value = expect_str(value)?;
+        Ok(tera::Value::String(base64::encode(value)))
+    }
+}
+
+pub struct B64DecodeFn {}
+
+impl tera::Filter for B64DecodeFn {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        _args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        let value = expect_str(value)?;
+        let decoded = base64::decode(value).map_err(|e| format!("invalid base64: {}", e))?;
+        let decoded = String::from_utf8(decoded).map_err(|_| "expect utf-8 content")?;
+        Ok(tera::Value::String(decoded))
+    }
+}
+
+// replaces characters which are unsafe in filenames by underscores and
+// truncates to the `max_length` argument, default 100,
+// e.g. {{ resource.name | sanitize }}.yaml
+pub struct SanitizeFn {}
+
+impl tera::Filter for SanitizeFn {
+    fn filter(
+        &self,
+        value: &tera::Value,
+        args: &HashMap<String, tera::Value>,
+    ) -> tera::Result<tera::Value> {
+        let max_length = match args.get("max_length") {
+            Some(max_length) => match max_length.as_u64() {
+                Some(max_length) => max_length as usize,
+                None => return Err("expect number as max_length".into()),
+            },
+            None => DEFAULT_MAX_LENGTH,
+        };
+        let value = expect_str(value)?;
+        Ok(tera::Value::String(sanitize(value, max_length)))
+    }
+}
+
+pub const DEFAULT_MAX_LENGTH: usize = 100;
+
+// sanitizes a single path component, a leading dot is replaced as well so
+// the result is neither hidden nor a relative path like "..", truncation
+// keeps the extension
+pub fn sanitize(value: &str, max_length: usize) -> String {
+    let sanitized: String = value
+        .chars()
+        .enumerate()
+        .map(|(idx, c)| {
+            let safe = c.is_ascii_alphanumeric() || c == '-' || c == '_' || (c == '.' && idx > 0);
+            if safe {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized.len() <= max_length {
+        return sanitized;
+    }
+    let extension = match sanitized.rfind('.') {
+        Some(pos) if sanitized.len() - pos < max_length => &sanitized[pos..],
+        _ => "",
+    };
+    format!("{}{}", &sanitized[..max_length - extension.len()], extension)
+}
+
+// sanitizes every component of a path, "." and ".." are kept as they are,
+// a path leaving the output directory is rejected when the file is placed
+pub fn sanitize_path(path: &str, max_length: usize) -> String {
+    path.split('/')
+        .map(|component| match component {
+            "" | "." | ".." => component.to_string(),
+            component => sanitize(component, max_length),
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+pub fn slugify(value: &str) -> String {
+    split_words(value, false).join("-")
+}
+
+fn expect_str(value: &tera::Value) -> tera::Result<&str> {
+    match value {
+        tera::Value::String(value) => Ok(value),
+        _ => Err("expect string".into()),
+    }
+}
+
+// splits at non alphanumeric characters and optionally at lower to upper case
+// transitions, returns the lowercased words
+fn split_words(value: &str, split_camel_case: bool) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut prev_lower = false;
+    for c in value.chars() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(word.clone());
+                word.clear();
+            }
+            prev_lower = false;
+            continue;
+        }
+        if split_camel_case && prev_lower && c.is_uppercase() && !word.is_empty() {
+            words.push(word.clone());
+            word.clear();
+        }
+        prev_lower = c.is_lowercase() || c.is_numeric();
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_path_keeps_relative_components() {
+        assert_eq!(
+            sanitize_path("../vendor/My Chart/config map.yaml", 255),
+            "../vendor/My_Chart/config_map.yaml"
+        );
+        assert_eq!(sanitize_path("./out//crds", 255), "./out//crds");
+        assert_eq!(sanitize_path("/abs/.hidden", 255), "/abs/_hidden");
+    }
+
+    #[test]
+    fn sanitize_path_replaces_backslashes() {
+        assert_eq!(sanitize_path("out\\crds\\service.yaml", 255), "out_crds_service.yaml");
+        assert_eq!(sanitize_path("out/crds\\service.yaml", 255), "out/crds_service.yaml");
+    }
+
+    #[test]
+    fn sanitize_path_truncates_components() {
+        assert_eq!(sanitize_path("out/abcdefghij.yaml", 10), "out/abcde.yaml");
+        assert_eq!(sanitize_path("abcdefghijkl/x.yaml", 10), "abcdefghij/x.yaml");
+    }
+}
@@ -0,0 +1,120 @@
+use crate::Matcher;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use yaml_rust::Yaml;
+
+// converts matching ConfigMaps and Secrets into configMapGenerator and
+// secretGenerator entries with every data key written to its own file
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct GeneratorRule {
+    #[serde(default)]
+    pub matcher: Matcher,
+    // directory of the data files relative to the package
+    #[serde(default = "default_dir_template")]
+    pub dirTemplate: String,
+    #[serde(default)]
+    disableNameSuffixHash: bool,
+}
+
+fn default_dir_template() -> String {
+    "{{resource.kind | lower}}-{{resource.name}}".to_string()
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GeneratorEntry {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    secret_type: Option<String>,
+    files: Vec<String>,
+    #[serde(skip_serializing_if = "GeneratorOptions::is_empty")]
+    options: GeneratorOptions,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Default)]
+struct GeneratorOptions {
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    disableNameSuffixHash: bool,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    labels: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    annotations: BTreeMap<String, String>,
+}
+
+impl GeneratorOptions {
+    fn is_empty(&self) -> bool {
+        !self.disableNameSuffixHash && self.labels.is_empty() && self.annotations.is_empty()
+    }
+}
+
+// data file of a generator as path relative to the package and content
+pub type DataFile = (String, Vec<u8>);
+
+// returns the generator entry and the data files of a ConfigMap or Secret,
+// None for any other kind
+pub fn to_generator(
+    rule: &GeneratorRule,
+    manifest: &Yaml,
+    dir: &str,
+) -> Option<(GeneratorEntry, Vec<DataFile>)> {
+    let kind = manifest["kind"].as_str()?;
+    if kind != "ConfigMap" && kind != "Secret" {
+        return None;
+    }
+
+    let mut files = Vec::new();
+    let (plain_field, encoded_field) = if kind == "Secret" {
+        ("stringData", "data")
+    } else {
+        ("data", "binaryData")
+    };
+    if let Some(data) = manifest[plain_field].as_hash() {
+        for (key, value) in data {
+            if let (Some(key), Some(value)) = (key.as_str(), value.as_str()) {
+                files.push((key.to_string(), value.as_bytes().to_vec()));
+            }
+        }
+    }
+    if let Some(data) = manifest[encoded_field].as_hash() {
+        for (key, value) in data {
+            if let (Some(key), Some(value)) = (key.as_str(), value.as_str()) {
+                let value: String = value.split_whitespace().collect();
+                files.push((key.to_string(), base64::decode(value).ok()?));
+            }
+        }
+    }
+
+    let files: Vec<DataFile> = files
+        .into_iter()
+        .map(|(key, content)| (format!("{}/{}", dir, key), content))
+        .collect();
+    let entry = GeneratorEntry {
+        name: manifest["metadata"]["name"].as_str()?.to_string(),
+        namespace: manifest["metadata"]["namespace"].as_str().map(|s| s.to_string()),
+        secret_type: manifest["type"].as_str().map(|s| s.to_string()),
+        files: files.iter().map(|(path, _)| path.clone()).collect(),
+        options: GeneratorOptions {
+            disableNameSuffixHash: rule.disableNameSuffixHash,
+            labels: string_map(&manifest["metadata"]["labels"]),
+            annotations: string_map(&manifest["metadata"]["annotations"]),
+        },
+    };
+    Some((entry, files))
+}
+
+fn string_map(yaml: &Yaml) -> BTreeMap<String, String> {
+    let mut map = BTreeMap::new();
+    if let Some(hash) = yaml.as_hash() {
+        for (key, value) in hash {
+            if let (Some(key), Some(value)) = (key.as_str(), value.as_str()) {
+                map.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+    map
+}
@@ -0,0 +1,318 @@
+use crate::{Config, Error, Package};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// Flux Kustomization object generated per package,
+// see https://fluxcd.io/flux/components/kustomize/kustomizations/
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FluxSpec {
+    pathTemplate: String,
+    #[serde(default = "default_filename_template")]
+    filenameTemplate: String,
+    #[serde(default = "default_name_template")]
+    nameTemplate: String,
+    // spec.path of the Kustomization relative to the source root
+    #[serde(default = "default_source_path_template")]
+    sourcePathTemplate: String,
+    #[serde(default = "default_flux_namespace")]
+    namespace: String,
+    #[serde(default = "default_interval")]
+    interval: String,
+    #[serde(default = "default_prune")]
+    prune: bool,
+    #[serde(default)]
+    sourceRef: FluxSourceRef,
+    targetNamespace: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct FluxSourceRef {
+    kind: String,
+    name: String,
+}
+
+impl Default for FluxSourceRef {
+    fn default() -> Self {
+        FluxSourceRef {
+            kind: "GitRepository".to_string(),
+            name: "flux-system".to_string(),
+        }
+    }
+}
+
+impl FluxSpec {
+    // templated fields by name
+    pub fn templates(&self) -> Vec<(&'static str, &str)> {
+        vec![
+            ("pathTemplate", &self.pathTemplate),
+            ("filenameTemplate", &self.filenameTemplate),
+            ("nameTemplate", &self.nameTemplate),
+            ("sourcePathTemplate", &self.sourcePathTemplate),
+        ]
+    }
+}
+
+fn default_filename_template() -> String {
+    "{{package.name}}.yaml".to_string()
+}
+
+fn default_name_template() -> String {
+    "{{top.name}}-{{package.name}}".to_string()
+}
+
+fn default_source_path_template() -> String {
+    "./{{package.path}}".to_string()
+}
+
+fn default_flux_namespace() -> String {
+    "flux-system".to_string()
+}
+
+fn default_interval() -> String {
+    "10m".to_string()
+}
+
+fn default_prune() -> bool {
+    true
+}
+
+// Argo CD Application generated per package,
+// see https://argo-cd.readthedocs.io/en/stable/operator-manual/declarative-setup/
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ArgoCDSpec {
+    pathTemplate: String,
+    #[serde(default = "default_filename_template")]
+    filenameTemplate: String,
+    #[serde(default = "default_name_template")]
+    nameTemplate: String,
+    repoURLTemplate: String,
+    #[serde(default = "default_target_revision_template")]
+    targetRevisionTemplate: String,
+    // source.path of the Application relative to the repository root
+    #[serde(default = "default_repo_path_template")]
+    sourcePathTemplate: String,
+    #[serde(default = "default_argocd_namespace")]
+    namespace: String,
+    #[serde(default = "default_project")]
+    project: String,
+    #[serde(default)]
+    destination: ArgoCDDestination,
+    // enables automated sync with pruning and self healing
+    #[serde(default)]
+    automated: bool,
+}
+
+impl ArgoCDSpec {
+    // templated fields by name
+    pub fn templates(&self) -> Vec<(&'static str, &str)> {
+        vec![
+            ("pathTemplate", &self.pathTemplate),
+            ("filenameTemplate", &self.filenameTemplate),
+            ("nameTemplate", &self.nameTemplate),
+            ("repoURLTemplate", &self.repoURLTemplate),
+            ("targetRevisionTemplate", &self.targetRevisionTemplate),
+            ("sourcePathTemplate", &self.sourcePathTemplate),
+        ]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct ArgoCDDestination {
+    server: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+}
+
+impl Default for ArgoCDDestination {
+    fn default() -> Self {
+        ArgoCDDestination {
+            server: "https://kubernetes.default.svc".to_string(),
+            namespace: None,
+        }
+    }
+}
+
+fn default_target_revision_template() -> String {
+    "HEAD".to_string()
+}
+
+fn default_repo_path_template() -> String {
+    "{{package.path}}".to_string()
+}
+
+fn default_argocd_namespace() -> String {
+    "argocd".to_string()
+}
+
+fn default_project() -> String {
+    "default".to_string()
+}
+
+#[derive(Serialize)]
+struct Metadata {
+    name: String,
+    namespace: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize)]
+struct FluxKustomizationSpec {
+    interval: String,
+    path: String,
+    prune: bool,
+    sourceRef: FluxSourceRef,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    targetNamespace: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize)]
+struct FluxKustomization {
+    apiVersion: String,
+    kind: String,
+    metadata: Metadata,
+    spec: FluxKustomizationSpec,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize)]
+struct ArgoCDSource {
+    repoURL: String,
+    targetRevision: String,
+    path: String,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize)]
+struct ArgoCDAutomated {
+    prune: bool,
+    selfHeal: bool,
+}
+
+#[derive(Serialize)]
+struct ArgoCDSyncPolicy {
+    automated: ArgoCDAutomated,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize)]
+struct ArgoCDApplicationSpec {
+    project: String,
+    source: ArgoCDSource,
+    destination: ArgoCDDestination,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    syncPolicy: Option<ArgoCDSyncPolicy>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize)]
+struct ArgoCDApplication {
+    apiVersion: String,
+    kind: String,
+    metadata: Metadata,
+    spec: ArgoCDApplicationSpec,
+}
+
+impl Config {
+    // returns path and content of a Flux Kustomization for every package,
+    // components are skipped as they cannot be applied on their own
+    pub(crate) fn render_flux(&self, packages: &[Package]) -> Result<Vec<(PathBuf, String)>, Error> {
+        let flux = match &self.Flux {
+            Some(flux) => flux,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut files = Vec::new();
+        for package in packages.iter().filter(|package| !package.component) {
+            let mut context = self.context(Some(package), package.rule_idx);
+            context.insert("packageName", &package.name);
+            context.insert("package", &package);
+            let render = |field: &str, template: &str| {
+                let template_name = format!("Flux.{}", field);
+                self.render_template(&template_name, template, &context)
+            };
+
+            let object = FluxKustomization {
+                apiVersion: "kustomize.toolkit.fluxcd.io/v1".to_string(),
+                kind: "Kustomization".to_string(),
+                metadata: Metadata {
+                    name: render("nameTemplate", &flux.nameTemplate)?,
+                    namespace: flux.namespace.clone(),
+                },
+                spec: FluxKustomizationSpec {
+                    interval: flux.interval.clone(),
+                    path: render("sourcePathTemplate", &flux.sourcePathTemplate)?,
+                    prune: flux.prune,
+                    sourceRef: flux.sourceRef.clone(),
+                    targetNamespace: flux.targetNamespace.clone(),
+                },
+            };
+            let pathname = render("pathTemplate", &flux.pathTemplate)?;
+            let filename = render("filenameTemplate", &flux.filenameTemplate)?;
+            let content = serde_yaml::to_string(&object).unwrap();
+            files.push((Path::new(&pathname).join(filename), content));
+        }
+        Ok(files)
+    }
+
+    // returns path and content of an Argo CD Application for every package,
+    // components are skipped as they cannot be applied on their own
+    pub(crate) fn render_argocd(&self, packages: &[Package]) -> Result<Vec<(PathBuf, String)>, Error> {
+        let argocd = match &self.ArgoCD {
+            Some(argocd) => argocd,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut files = Vec::new();
+        for package in packages.iter().filter(|package| !package.component) {
+            let mut context = self.context(Some(package), package.rule_idx);
+            context.insert("packageName", &package.name);
+            context.insert("package", &package);
+            let render = |field: &str, template: &str| {
+                let template_name = format!("ArgoCD.{}", field);
+                self.render_template(&template_name, template, &context)
+            };
+
+            let sync_policy = if argocd.automated {
+                Some(ArgoCDSyncPolicy {
+                    automated: ArgoCDAutomated {
+                        prune: true,
+                        selfHeal: true,
+                    },
+                })
+            } else {
+                None
+            };
+            let object = ArgoCDApplication {
+                apiVersion: "argoproj.io/v1alpha1".to_string(),
+                kind: "Application".to_string(),
+                metadata: Metadata {
+                    name: render("nameTemplate", &argocd.nameTemplate)?,
+                    namespace: argocd.namespace.clone(),
+                },
+                spec: ArgoCDApplicationSpec {
+                    project: argocd.project.clone(),
+                    source: ArgoCDSource {
+                        repoURL: render("repoURLTemplate", &argocd.repoURLTemplate)?,
+                        targetRevision: render(
+                            "targetRevisionTemplate",
+                            &argocd.targetRevisionTemplate,
+                        )?,
+                        path: render("sourcePathTemplate", &argocd.sourcePathTemplate)?,
+                    },
+                    destination: argocd.destination.clone(),
+                    syncPolicy: sync_policy,
+                },
+            };
+            let pathname = render("pathTemplate", &argocd.pathTemplate)?;
+            let filename = render("filenameTemplate", &argocd.filenameTemplate)?;
+            let content = serde_yaml::to_string(&object).unwrap();
+            files.push((Path::new(&pathname).join(filename), content));
+        }
+        Ok(files)
+    }
+}
@@ -0,0 +1,343 @@
+use crate::manifest;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use tracing::{debug, warn};
+use yaml_rust::Yaml;
+
+const DEFAULT_REGISTRY: &str = "docker.io";
+
+// rewrites images of a registry to another registry,
+// e.g. docker.io/library/nginx -> mirror.internal/library/nginx
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ImageRewrite {
+    registry: String,
+    newRegistry: String,
+}
+
+// resolves image tags to digests via the registry API, either emitted as
+// images transformer entries or written into the manifests
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct DigestPinning {
+    #[serde(default)]
+    pub rewriteManifests: bool,
+}
+
+// entry of the kustomize images transformer
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ImageEntry {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub newName: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub newTag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+}
+
+// image reference split into its parts, the registry is None if the
+// reference relies on the default registry
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageRef {
+    pub registry: Option<String>,
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+impl ImageRef {
+    pub fn parse(image: &str) -> ImageRef {
+        let (rest, digest) = match image.find('@') {
+            Some(idx) => (&image[..idx], Some(image[idx + 1..].to_string())),
+            None => (image, None),
+        };
+        // a colon after the last slash separates the tag, a colon before it
+        // belongs to the registry port
+        let (name, tag) = match rest.rfind(':') {
+            Some(idx) if !rest[idx..].contains('/') => {
+                (&rest[..idx], Some(rest[idx + 1..].to_string()))
+            }
+            _ => (rest, None),
+        };
+        let (registry, repository) = match name.find('/') {
+            Some(idx)
+                if name[..idx].contains('.')
+                    || name[..idx].contains(':')
+                    || &name[..idx] == "localhost" =>
+            {
+                (Some(name[..idx].to_string()), name[idx + 1..].to_string())
+            }
+            _ => (None, name.to_string()),
+        };
+        ImageRef {
+            registry,
+            repository,
+            tag,
+            digest,
+        }
+    }
+
+    // name as written in the manifest without tag and digest
+    pub fn name(&self) -> String {
+        match &self.registry {
+            Some(registry) => format!("{}/{}", registry, self.repository),
+            None => self.repository.clone(),
+        }
+    }
+
+    pub fn registry(&self) -> &str {
+        self.registry.as_deref().unwrap_or(DEFAULT_REGISTRY)
+    }
+
+    // repository including the implicit library/ prefix of the default registry
+    pub fn full_repository(&self) -> String {
+        if self.registry() == DEFAULT_REGISTRY && !self.repository.contains('/') {
+            format!("library/{}", self.repository)
+        } else {
+            self.repository.clone()
+        }
+    }
+}
+
+// returns the kustomize images entries for all images matched by a rewrite
+// or pinned to a digest
+pub fn image_entries(
+    images: &[String],
+    rewrites: &[ImageRewrite],
+    digests: &BTreeMap<String, String>,
+) -> Vec<ImageEntry> {
+    let mut entries: Vec<ImageEntry> = Vec::new();
+    for image_str in images {
+        let image = ImageRef::parse(image_str);
+        let rewrite = rewrites
+            .iter()
+            .find(|rewrite| rewrite.registry == image.registry());
+        let digest = digests.get(image_str);
+        if rewrite.is_none() && digest.is_none() {
+            continue;
+        }
+        let entry = ImageEntry {
+            name: image.name(),
+            newName: rewrite
+                .map(|rewrite| format!("{}/{}", rewrite.newRegistry, image.full_repository())),
+            newTag: None,
+            digest: digest.cloned(),
+        };
+        if !entries.contains(&entry) {
+            entries.push(entry);
+        }
+    }
+    entries
+}
+
+// returns the images of all containers, init containers and ephemeral
+// containers found anywhere in the manifest
+pub fn find_images(manifest: &Yaml) -> Vec<String> {
+    let mut images = Vec::new();
+    collect_images(manifest, &mut images);
+    images
+}
+
+fn collect_images(yaml: &Yaml, images: &mut Vec<String>) {
+    match yaml {
+        Yaml::Hash(hash) => {
+            for (key, value) in hash {
+                let is_container_list = match key.as_str() {
+                    Some(key) => {
+                        key == "containers" || key == "initContainers" || key == "ephemeralContainers"
+                    }
+                    None => false,
+                };
+                if let (true, Yaml::Array(containers)) = (is_container_list, value) {
+                    for container in containers {
+                        if let Some(image) = container["image"].as_str() {
+                            if !images.iter().any(|known| known == image) {
+                                images.push(image.to_string());
+                            }
+                        }
+                    }
+                }
+                collect_images(value, images);
+            }
+        }
+        Yaml::Array(array) => {
+            for value in array {
+                collect_images(value, images);
+            }
+        }
+        _ => {}
+    }
+}
+
+// replaces every container image of the manifest which has a resolved digest
+// by its digest pinned reference
+pub fn pin_images(yaml: &mut Yaml, digests: &BTreeMap<String, String>) {
+    match yaml {
+        Yaml::Hash(hash) => {
+            for (key, value) in hash.iter_mut() {
+                let is_container_list = match key.as_str() {
+                    Some(key) => {
+                        key == "containers" || key == "initContainers" || key == "ephemeralContainers"
+                    }
+                    None => false,
+                };
+                if let (true, Yaml::Array(containers)) = (is_container_list, &mut *value) {
+                    for container in containers.iter_mut() {
+                        pin_container_image(container, digests);
+                    }
+                }
+                pin_images(value, digests);
+            }
+        }
+        Yaml::Array(array) => {
+            for value in array.iter_mut() {
+                pin_images(value, digests);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn pin_container_image(container: &mut Yaml, digests: &BTreeMap<String, String>) {
+    if let Yaml::Hash(container) = container {
+        let key = Yaml::String("image".to_string());
+        let pinned = match container.get(&key).and_then(|image| image.as_str()) {
+            Some(image) => digests
+                .get(image)
+                .map(|digest| format!("{}@{}", ImageRef::parse(image).name(), digest)),
+            None => None,
+        };
+        if let Some(pinned) = pinned {
+            manifest::set_entry(container, key, Yaml::String(pinned));
+        }
+    }
+}
+
+// resolves image tags to manifest digests, results are cached per run
+#[derive(Default)]
+pub struct DigestResolver {
+    client: reqwest::blocking::Client,
+    digests: HashMap<String, Option<String>>,
+    // number of images whose digest could not be resolved
+    pub failures: usize,
+}
+
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+application/vnd.docker.distribution.manifest.list.v2+json, \
+application/vnd.oci.image.manifest.v1+json, \
+application/vnd.docker.distribution.manifest.v2+json";
+
+impl DigestResolver {
+    // returns the digests of all given images which could be resolved,
+    // images already referenced by digest are skipped
+    pub fn resolve_all(&mut self, images: &[String]) -> BTreeMap<String, String> {
+        let mut digests = BTreeMap::new();
+        for image in images {
+            if let Some(digest) = self.resolve(image) {
+                digests.insert(image.clone(), digest);
+            }
+        }
+        digests
+    }
+
+    pub fn resolve(&mut self, image: &str) -> Option<String> {
+        if let Some(digest) = self.digests.get(image) {
+            return digest.clone();
+        }
+        let image_ref = ImageRef::parse(image);
+        let digest = if image_ref.digest.is_some() {
+            None
+        } else {
+            match self.fetch_digest(&image_ref) {
+                Ok(digest) => {
+                    debug!("resolve image {} to {}", image, digest);
+                    Some(digest)
+                }
+                Err(e) => {
+                    warn!("unable to resolve digest of image {}: {}", image, e);
+                    self.failures += 1;
+                    None
+                }
+            }
+        };
+        self.digests.insert(image.to_string(), digest.clone());
+        digest
+    }
+
+    fn fetch_digest(&self, image: &ImageRef) -> Result<String, Box<dyn Error>> {
+        let host = match image.registry() {
+            DEFAULT_REGISTRY => "registry-1.docker.io",
+            registry => registry,
+        };
+        let url = format!(
+            "https://{}/v2/{}/manifests/{}",
+            host,
+            image.full_repository(),
+            image.tag.as_deref().unwrap_or("latest")
+        );
+        debug!("fetch digest {}", url);
+
+        let mut resp = self
+            .client
+            .head(&url)
+            .header("Accept", MANIFEST_MEDIA_TYPES)
+            .send()?;
+        if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+            // anonymous pull token as announced by the registry
+            let challenge = resp
+                .headers()
+                .get("www-authenticate")
+                .and_then(|challenge| challenge.to_str().ok())
+                .ok_or("registry requires authentication")?
+                .to_string();
+            let token = self.fetch_token(&challenge)?;
+            resp = self
+                .client
+                .head(&url)
+                .header("Accept", MANIFEST_MEDIA_TYPES)
+                .bearer_auth(token)
+                .send()?;
+        }
+        if !resp.status().is_success() {
+            return Err(format!("registry returned {}", resp.status()).into());
+        }
+        let digest = resp
+            .headers()
+            .get("docker-content-digest")
+            .ok_or("registry returned no digest")?
+            .to_str()?;
+        Ok(digest.to_string())
+    }
+
+    // requests a token for a challenge like
+    // Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="..."
+    fn fetch_token(&self, challenge: &str) -> Result<String, Box<dyn Error>> {
+        let params = challenge
+            .strip_prefix("Bearer ")
+            .ok_or("unsupported authentication scheme")?;
+        let mut realm = None;
+        let mut query = Vec::new();
+        for param in params.split("\",") {
+            let mut parts = param.splitn(2, '=');
+            let key = parts.next().unwrap_or_default().trim_start_matches(',');
+            let value = parts.next().unwrap_or_default().trim_matches('"');
+            if key == "realm" {
+                realm = Some(value.to_string());
+            } else {
+                query.push((key.to_string(), value.to_string()));
+            }
+        }
+        let realm = realm.ok_or("authentication challenge without realm")?;
+        let body = self.client.get(&realm).query(&query).send()?.text()?;
+        let body: serde_json::Value = serde_json::from_str(&body)?;
+        let token = body["token"]
+            .as_str()
+            .or_else(|| body["access_token"].as_str())
+            .ok_or("token response without token")?;
+        Ok(token.to_string())
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{deserialize, interpolate};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// merges the fragments listed in the include field of a config into it, None
+// if it includes nothing, fields of the including config win over those of
+// the fragments, earlier fragments over later ones, lists are concatenated
+// in the same order, e.g. the split rules of the config come first
+pub fn compose(config_path: &Path, config_text: &str) -> Result<Option<Value>, String> {
+    let mut config: Value = deserialize(config_path, config_text)
+        .map_err(|err| format!("{}: {}", config_path.display(), err))?;
+    if included(&config, config_path)?.is_empty() {
+        return Ok(None);
+    }
+    interpolate::expand_env(&mut config).map_err(|err| format!("{}: {}", config_path.display(), err))?;
+    let mut stack = vec![canonical(config_path)];
+    resolve(&mut config, config_path, &mut stack)?;
+    Ok(Some(config))
+}
+
+fn resolve(config: &mut Value, path: &Path, stack: &mut Vec<PathBuf>) -> Result<(), String> {
+    // fragments are resolved relative to the file including them
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    for include in included(config, path)? {
+        let include_path = base_dir.join(include);
+        if stack.contains(&canonical(&include_path)) {
+            return Err(format!("{} includes itself", include_path.display()));
+        }
+        let mut fragment = read(&include_path)?;
+        stack.push(canonical(&include_path));
+        resolve(&mut fragment, &include_path, stack)?;
+        stack.pop();
+        if let Value::Object(fields) = &mut fragment {
+            fields.remove("include");
+        }
+        merge(config, fragment);
+    }
+    Ok(())
+}
+
+fn included(config: &Value, path: &Path) -> Result<Vec<String>, String> {
+    match config.get("include") {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_str().map(String::from))
+            .collect::<Option<Vec<String>>>()
+            .ok_or_else(|| format!("{}: include must be a list of paths", path.display())),
+        Some(_) => Err(format!("{}: include must be a list of paths", path.display())),
+    }
+}
+
+fn read(path: &Path) -> Result<Value, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("unable to read {}: {}", path.display(), err))?;
+    let mut fragment = deserialize(path, &text).map_err(|err| format!("{}: {}", path.display(), err))?;
+    interpolate::expand_env(&mut fragment).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(fragment)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn merge(config: &mut Value, fragment: Value) {
+    match (config, fragment) {
+        (Value::Object(fields), Value::Object(fragment_fields)) => {
+            for (key, value) in fragment_fields {
+                match fields.get_mut(&key) {
+                    Some(field) => merge(field, value),
+                    None => {
+                        fields.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(items), Value::Array(fragment_items)) => items.extend(fragment_items),
+        (config @ Value::Null, fragment) => *config = fragment,
+        _ => {}
+    }
+}
@@ -0,0 +1,134 @@
+use crate::migrate;
+use std::collections::BTreeMap;
+use yaml_rust::Yaml;
+
+// kinds which get their own package in the starter config when present
+pub const SUGGESTED_PACKAGES: &[(&str, &[&str])] = &[
+    ("crds", &["CustomResourceDefinition"]),
+    ("namespaces", &["Namespace"]),
+    (
+        "rbac",
+        &["ServiceAccount", "ClusterRole", "ClusterRoleBinding", "Role", "RoleBinding"],
+    ),
+    (
+        "webhooks",
+        &["MutatingWebhookConfiguration", "ValidatingWebhookConfiguration"],
+    ),
+];
+
+// returns a starter config for the manifests downloaded from url, with a
+// split rule per suggested package whose kinds occur in the manifests
+pub fn starter_config(url: &str, manifests: &[Yaml]) -> String {
+    let mut kinds: BTreeMap<String, usize> = BTreeMap::new();
+    let mut namespaces: Vec<String> = Vec::new();
+    for manifest in manifests {
+        if let Some(kind) = manifest["kind"].as_str() {
+            *kinds.entry(kind.to_string()).or_default() += 1;
+        }
+        if let Some(namespace) = manifest["metadata"]["namespace"].as_str() {
+            if !namespaces.iter().any(|known| known == namespace) {
+                namespaces.push(namespace.to_string());
+            }
+        }
+    }
+
+    let (name, version, source_template) = describe_url(url);
+    let mut config = String::new();
+    config.push_str(&format!("# starter config generated from {}\n", url));
+    config.push_str("# the upstream contains:\n");
+    for (kind, count) in &kinds {
+        config.push_str(&format!("#   {} {}\n", count, kind));
+    }
+    if !namespaces.is_empty() {
+        config.push_str(&format!("# in the namespaces {}\n", namespaces.join(", ")));
+    }
+    config.push_str(&format!("apiVersion: {}\n", migrate::API_VERSION));
+    config.push_str(&format!(
+        "Top:
+  name: {}
+  version: {}
+  sourceTemplate: {}
+DefaultPackageSpec:
+  template: |
+    apiVersion: {{{{package.apiVersion}}}}
+    kind: {{{{package.kind}}}}
+    resources:
+    {{% for file in package.files %}}  - {{{{file}}}}
+    {{% endfor %}}
+  pathTemplate: \"{{{{top.name}}}}-{{{{top.version}}}}/{{{{packageName}}}}\"
+  filenameTemplate: kustomization.yaml
+  defaultName: main
+  resourceSpec:
+    pathTemplate: \"{{{{top.name}}}}-{{{{top.version}}}}/{{{{packageName}}}}\"
+    filenameTemplate: \"{{{{resource.kind | kebab}}}}-{{{{resource.name | slugify}}}}.yaml\"
+",
+        name, version, source_template
+    ));
+
+    let mut rules = String::new();
+    for (package, package_kinds) in SUGGESTED_PACKAGES {
+        for kind in package_kinds.iter().filter(|kind| kinds.contains_key(**kind)) {
+            rules.push_str(&format!(
+                "  - matcher:\n      kind: {}\n    packageName: {}\n",
+                kind, package
+            ));
+        }
+    }
+    if rules.is_empty() {
+        config.push_str("SplitRules: []\n");
+    } else {
+        config.push_str("SplitRules:\n");
+        config.push_str(&rules);
+    }
+    if namespaces.len() > 1 {
+        config.push_str(
+            "  # the resources span several namespaces, to split them per namespace add
+  # - splitBy: namespace
+",
+        );
+    }
+    config.push_str(
+        "RootSpec:
+  template: |
+    resources:
+    {% for package in resources %}  - {{package.relativePath}}
+    {% endfor %}
+  pathTemplate: \"{{top.name}}-{{top.version}}\"
+  filenameTemplate: kustomization.yaml
+",
+    );
+    config
+}
+
+// name, version and source template derived from a url like
+// https://github.com/org/project/releases/download/v1.2.3/install.yaml, the
+// version in the url is replaced by {{top.version}}
+fn describe_url(url: &str) -> (String, String, String) {
+    let segments: Vec<&str> = url.split('/').filter(|segment| !segment.is_empty()).collect();
+    let version = segments
+        .iter()
+        .map(|segment| segment.trim_start_matches('v'))
+        .find(|segment| is_version(segment))
+        .map(String::from);
+    let name = match url.find("github.com/").or_else(|| url.find("githubusercontent.com/")) {
+        Some(_) if segments.len() > 3 => segments[3].to_string(),
+        _ => segments
+            .last()
+            .map(|file| file.split('.').next().unwrap_or(file).to_string())
+            .unwrap_or_else(|| "upstream".to_string()),
+    };
+    match version {
+        Some(version) => {
+            let source_template = url.replace(&version, "{{top.version}}");
+            (name, version, source_template)
+        }
+        None => (name, "0.0.0".to_string(), url.to_string()),
+    }
+}
+
+// true for versions like 1.2 or 1.2.3, optionally with a suffix like -rc.1
+fn is_version(segment: &str) -> bool {
+    let core = segment.split('-').next().unwrap_or_default();
+    let parts: Vec<&str> = core.split('.').collect();
+    parts.len() >= 2 && parts.iter().all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
@@ -0,0 +1,72 @@
+use serde_json::Value;
+
+// expands ${VAR} and ${VAR:-default} with environment variables in the string
+// values of a parsed config, so comments and keys are left alone, $${ is kept
+// as a literal ${, unset variables without a default are an error
+pub fn expand_env(value: &mut Value) -> Result<(), String> {
+    expand_field(value, "")
+}
+
+// errors name the field, e.g. SplitRules[0].packageName
+fn expand_field(value: &mut Value, field: &str) -> Result<(), String> {
+    match value {
+        Value::String(text) => {
+            *text = expand(text, |name| std::env::var(name).ok()).map_err(|err| format!("{}: {}", field, err))?
+        }
+        Value::Array(items) => {
+            for (idx, item) in items.iter_mut().enumerate() {
+                expand_field(item, &format!("{}[{}]", field, idx))?;
+            }
+        }
+        Value::Object(fields) => {
+            for (key, item) in fields {
+                match field {
+                    "" => expand_field(item, key)?,
+                    field => expand_field(item, &format!("{}.{}", field, key))?,
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn expand(text: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with("$${") {
+            expanded.push_str("${");
+            rest = &rest[3..];
+        } else if let Some(body) = rest.strip_prefix("${") {
+            let end = body
+                .find('}')
+                .ok_or_else(|| format!("unterminated ${{ in {}", first_line(rest)))?;
+            let (name, default) = match body[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&body[..end], None),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("invalid variable name in {}", first_line(rest)));
+            }
+            // like in the shell the default also replaces an empty value
+            let value = lookup(name).filter(|value| !value.is_empty() || default.is_none());
+            match value.or_else(|| default.map(String::from)) {
+                Some(value) => expanded.push_str(&value),
+                None => return Err(format!("environment variable {} is not set", name)),
+            }
+            rest = &body[end + 1..];
+        } else {
+            expanded.push('$');
+            rest = &rest[1..];
+        }
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
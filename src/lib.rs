@@ -0,0 +1,3280 @@
+//! Splits upstream kubernetes manifests into kustomize packages using split
+//! rules, see [`split_manifests`] to embed it and [`run`] for the command line.
+
+use rayon::prelude::*;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tera::{Context, Tera};
+use tracing::{debug, info, trace, warn};
+use yaml_merge_keys::merge_keys;
+use yaml_rust::Yaml;
+
+pub use error::Error;
+pub use progress::Progress;
+
+mod archive;
+mod bump;
+mod changelog;
+mod deprecations;
+mod emitter;
+mod error;
+mod fetch;
+pub mod filters;
+mod generators;
+mod gitops;
+mod images;
+mod includes;
+mod init;
+mod interpolate;
+mod manifest;
+mod migrate;
+mod output;
+mod overrides;
+mod patches;
+mod progress;
+mod replacements;
+pub mod report;
+mod review;
+mod schemas;
+mod snapshot;
+mod suggest;
+mod validate;
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Config {
+    // shape of the config, see migrate, unset for configs predating it
+    apiVersion: Option<String>,
+    // config fragments merged into this one, e.g. shared split rules, paths
+    // are relative to the config, template files of fragments are still
+    // relative to the including config
+    #[serde(default)]
+    include: Vec<String>,
+    // named overlays over this config selected with --profile, e.g. other
+    // versions or output directories per environment
+    #[serde(default)]
+    Profiles: BTreeMap<String, serde_json::Value>,
+    // unset if the config lists its upstreams
+    #[serde(default)]
+    Top: Top,
+    // upstreams vendored in one run, which share everything but their Top
+    // and package specs, outputDir is taken from the Top of the config
+    #[serde(default)]
+    Upstreams: Vec<Upstream>,
+    DefaultPackageSpec: DefaultPackageSpec,
+    #[serde(default)]
+    PackageSpecs: HashMap<String, PackageSpec>,
+    SplitRules: Vec<SplitRule>,
+    #[serde(default)]
+    Variables: HashMap<String, tera::Value>,
+    Templates: Option<Templates>,
+    RootSpec: Option<RootSpec>,
+    #[serde(default)]
+    Overlays: HashMap<String, OverlaySpec>,
+    Flux: Option<gitops::FluxSpec>,
+    ArgoCD: Option<gitops::ArgoCDSpec>,
+    #[serde(default)]
+    ImageRewrites: Vec<images::ImageRewrite>,
+    PinImageDigests: Option<images::DigestPinning>,
+    #[serde(default)]
+    Generators: Vec<generators::GeneratorRule>,
+    #[serde(default)]
+    Replacements: Vec<replacements::Replacement>,
+    #[serde(default)]
+    Patches: Vec<patches::PatchRule>,
+    StripFields: Option<StripFields>,
+    // remove the labels and annotations claiming helm ownership
+    #[serde(default)]
+    StripHelmMetadata: bool,
+    #[serde(default)]
+    IndexMode: IndexMode,
+    #[serde(default)]
+    FilenameCollisions: Collisions,
+    Sanitize: Option<Sanitize>,
+    // reject resource paths which are invalid on Windows, always on when
+    // running on Windows
+    #[serde(default)]
+    PortablePaths: bool,
+    // paths relative to the output directory which --prune never deletes
+    #[serde(default)]
+    PruneKeep: Vec<String>,
+    // order namespaces and CRDs before the resources depending on them
+    #[serde(default)]
+    CRDsFirst: bool,
+    // fail instead of skipping documents which are not valid resources
+    #[serde(default)]
+    StrictDocuments: bool,
+    // skip documents which fail to parse instead of aborting the run
+    #[serde(default)]
+    LenientParsing: bool,
+    Provenance: Option<Provenance>,
+    // changes of a run to the previously generated resources
+    Changelog: Option<changelog::ChangelogSpec>,
+    #[serde(default)]
+    Emitter: emitter::EmitterSpec,
+    // check the manifests against kubernetes json schemas before writing them
+    SchemaValidation: Option<schemas::SchemaValidation>,
+    // kubernetes version the packages are applied to, e.g. 1.29, resources
+    // with an apiVersion it deprecated are reported, all deprecations if unset,
+    // and SchemaValidation checks against its schemas
+    KubernetesVersion: Option<String>,
+    // rewrite deprecated apiVersions to their replacement where the
+    // conversion is mechanical, e.g. rbac.authorization.k8s.io/v1beta1 to v1
+    #[serde(default)]
+    UpgradeApiVersions: bool,
+    // template environment shared by all renders
+    #[serde(skip)]
+    tera: Tera,
+    // templates of the config compiled into tera by name and source
+    #[serde(skip)]
+    compiled: HashMap<String, String>,
+    // directory of the config file, Templates.dir is relative to it
+    #[serde(skip)]
+    base_dir: PathBuf,
+    // tera holds the filters and templates, false for a config deserialized
+    // by a library user instead of loaded
+    #[serde(skip)]
+    prepared: bool,
+    // classify scripts of the split rules compiled with the templates
+    #[serde(skip)]
+    scripts: Arc<ClassifyScripts>,
+}
+
+// what resource.index counts
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+enum IndexMode {
+    // position of the document in the upstream, skipped documents leave gaps
+    #[default]
+    Document,
+    // resources written to at least one package, without gaps
+    Resource,
+}
+
+// replaces unsafe characters in rendered resource filenames and paths
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct Sanitize {
+    // maximum length of every path component
+    #[serde(default = "default_max_length")]
+    maxLength: usize,
+}
+
+fn default_max_length() -> usize {
+    filters::DEFAULT_MAX_LENGTH
+}
+
+// what happens if resources render the same file
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+enum Collisions {
+    #[default]
+    Fail,
+    // append -1, -2, ... to the filename of later resources
+    Suffix,
+}
+
+// server side fields removed from every manifest before it is written
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct StripFields {
+    // dotted paths into the manifest
+    #[serde(default = "default_strip_fields")]
+    fields: Vec<String>,
+}
+
+fn default_strip_fields() -> Vec<String> {
+    vec![
+        "status".to_string(),
+        "metadata.creationTimestamp".to_string(),
+        "metadata.managedFields".to_string(),
+        "metadata.uid".to_string(),
+        "metadata.resourceVersion".to_string(),
+        "metadata.generation".to_string(),
+        "metadata.selfLink".to_string(),
+    ]
+}
+
+// annotations tracing every written manifest back to the upstream
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct Provenance {
+    #[serde(default = "default_provenance_prefix")]
+    annotationPrefix: String,
+    // the generation time changes on every run, so it can be turned off
+    #[serde(default = "default_true")]
+    timestamp: bool,
+}
+
+// prefix of the annotations added by kustomize-upstream
+const ANNOTATION_PREFIX: &str = "kustomize-upstream.io/";
+
+// resources emitted and written at once
+const EMIT_BATCH: usize = 1024;
+
+fn default_provenance_prefix() -> String {
+    ANNOTATION_PREFIX.to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// descriptor rendered once with all packages, e.g. a root kustomization.yaml
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct RootSpec {
+    #[serde(default)]
+    template: String,
+    templateFile: Option<String>,
+    #[serde(default)]
+    filenameTemplate: String,
+    filenameTemplateFile: Option<String>,
+    #[serde(default)]
+    pathTemplate: String,
+    pathTemplateFile: Option<String>,
+}
+
+// overlay scaffolded once per environment referencing the generated packages,
+// existing overlay files are never overwritten
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct OverlaySpec {
+    #[serde(default)]
+    template: String,
+    templateFile: Option<String>,
+    filenameTemplate: String,
+    pathTemplate: String,
+    // names of the referenced packages, all packages if not given
+    packages: Option<Vec<String>>,
+    #[serde(default)]
+    patches: Vec<ExtraFile>,
+    #[serde(default)]
+    vars: HashMap<String, tera::Value>,
+}
+
+// directory of templates, macros and includes usable from every template
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct Templates {
+    dir: String,
+}
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct Upstream {
+    Top: Top,
+    // replaces the DefaultPackageSpec of the config
+    DefaultPackageSpec: Option<DefaultPackageSpec>,
+    // added to the PackageSpecs of the config, replacing specs of the same name
+    #[serde(default)]
+    PackageSpecs: HashMap<String, PackageSpec>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Default)]
+struct Top {
+    // required, defaults only allow a Top holding just outputDir next to Upstreams
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    version: String,
+    // versions generated side by side, each replacing version in turn,
+    // instead of version
+    #[serde(default)]
+    versions: Vec<String>,
+    #[serde(default)]
+    sourceTemplate: String,
+    source: Option<String>,
+    // directory all rendered paths are relative to, --output-dir overrides it
+    outputDir: Option<String>,
+    // path of a byte-for-byte copy of the downloaded upstream manifests
+    mirrorPathTemplate: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct DefaultPackageSpec {
+    defaultName: String,
+    #[serde(flatten)]
+    spec: PackageSpec,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct PackageSpec {
+    #[serde(default)]
+    template: String,
+    templateFile: Option<String>,
+    #[serde(default)]
+    filenameTemplate: String,
+    filenameTemplateFile: Option<String>,
+    #[serde(default)]
+    pathTemplate: String,
+    pathTemplateFile: Option<String>,
+    resourceSpec: ResourceSpec,
+    #[serde(default)]
+    vars: HashMap<String, tera::Value>,
+    #[serde(default)]
+    extraFiles: Vec<ExtraFile>,
+    // packages of this spec are kustomize components
+    #[serde(default)]
+    component: bool,
+    // appends `namespace: <commonNamespace>` to the descriptor if the
+    // template does not set a namespace itself
+    #[serde(default)]
+    autoNamespace: bool,
+    // templated labels and annotations appended to the descriptor
+    #[serde(default)]
+    commonLabels: BTreeMap<String, String>,
+    #[serde(default)]
+    commonAnnotations: BTreeMap<String, String>,
+    // additionally sets the common labels and annotations on every resource
+    #[serde(default)]
+    stampCommonMetadata: bool,
+    // templated name prefix and suffix appended to the descriptor
+    namePrefix: Option<String>,
+    nameSuffix: Option<String>,
+    patchStubs: Option<PatchStubs>,
+    // order of package.resources when the descriptor is rendered
+    #[serde(default)]
+    sortBy: SortBy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+enum SortBy {
+    // the order the resources were encountered in the upstream
+    #[default]
+    None,
+    Index,
+    // kind, then namespace and name
+    Kind,
+    // name, then kind and namespace
+    Name,
+}
+
+// empty strategic merge patches scaffolded once per workload and wired into
+// the descriptor, existing patch files are never overwritten
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct PatchStubs {
+    #[serde(default = "default_patch_stub_kinds")]
+    kinds: Vec<String>,
+    // path relative to the package descriptor
+    #[serde(default = "default_patch_stub_filename_template")]
+    filenameTemplate: String,
+}
+
+fn default_patch_stub_kinds() -> Vec<String> {
+    vec![
+        "Deployment".to_string(),
+        "DaemonSet".to_string(),
+        "StatefulSet".to_string(),
+    ]
+}
+
+fn default_patch_stub_filename_template() -> String {
+    "patches/{{resource.kind | kebab}}-{{resource.name}}.yaml".to_string()
+}
+
+// additional file rendered once per package next to its descriptor,
+// e.g. a README or OWNERS file
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct ExtraFile {
+    filenameTemplate: String,
+    #[serde(default)]
+    template: String,
+    templateFile: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct ResourceSpec {
+    #[serde(default)]
+    pathTemplate: String,
+    pathTemplateFile: Option<String>,
+    #[serde(default)]
+    filenameTemplate: String,
+    filenameTemplateFile: Option<String>,
+    // what resource.index is counted within
+    #[serde(default)]
+    indexScope: IndexScope,
+    // writes the resources of a package, or of a kind within a package, into
+    // one multi-document file named by groupFilenameTemplate instead of
+    // filenameTemplate
+    #[serde(default)]
+    groupBy: GroupBy,
+    groupFilenameTemplate: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+enum GroupBy {
+    // one file per resource
+    #[default]
+    None,
+    // resources.yaml by default
+    Package,
+    // e.g. cluster-role.yaml by default
+    Kind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+enum IndexScope {
+    // the whole upstream, see IndexMode
+    #[default]
+    Global,
+    // the resources of the package
+    Package,
+    // the resources of the same kind within the package
+    Kind,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct SplitRule {
+    #[serde(default)]
+    matcher: Matcher,
+    packageName: Option<String>,
+    packageNames: Option<Vec<String>>,
+    packageNameTemplate: Option<String>,
+    splitBy: Option<SplitBy>,
+    // package of namespaced resources without a namespace when splitting by
+    // namespace, the default package if unset
+    unnamespacedPackageName: Option<String>,
+    classify: Option<String>,
+    resourceSpec: Option<ResourceSpec>,
+    packageSpec: Option<String>,
+    // namespace replacing the one of matched namespaced resources
+    setNamespace: Option<String>,
+    rename: Option<Rename>,
+    // templated labels and annotations added to matched resources
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+    #[serde(default)]
+    vars: HashMap<String, tera::Value>,
+}
+
+// renames matched resources, the original name is kept in an annotation
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct Rename {
+    nameTemplate: String,
+}
+
+// shorthand grouping matched resources into one package per value, by
+// namespace the cluster scoped resources go to packageName(s) of the rule
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+enum SplitBy {
+    Namespace,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Default)]
+struct Matcher {
+    kind: Option<String>,
+    name: Option<String>,
+    namespace: Option<String>,
+    scope: Option<Scope>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+enum Scope {
+    Cluster,
+    Namespaced,
+}
+
+// kinds which are cluster scoped even though they carry no namespace field
+const CLUSTER_SCOPED_KINDS: &[&str] = &[
+    "apiservice",
+    "certificatesigningrequest",
+    "clusterrole",
+    "clusterrolebinding",
+    "componentstatus",
+    "csidriver",
+    "csinode",
+    "customresourcedefinition",
+    "flowschema",
+    "ingressclass",
+    "mutatingwebhookconfiguration",
+    "namespace",
+    "node",
+    "persistentvolume",
+    "podsecuritypolicy",
+    "priorityclass",
+    "prioritylevelconfiguration",
+    "runtimeclass",
+    "storageclass",
+    "validatingadmissionpolicy",
+    "validatingadmissionpolicybinding",
+    "validatingwebhookconfiguration",
+    "volumeattachment",
+];
+
+// kinds which are namespaced even if the upstream omits metadata.namespace
+const NAMESPACED_KINDS: &[&str] = &[
+    "configmap",
+    "cronjob",
+    "daemonset",
+    "deployment",
+    "endpoints",
+    "endpointslice",
+    "horizontalpodautoscaler",
+    "ingress",
+    "job",
+    "lease",
+    "limitrange",
+    "networkpolicy",
+    "persistentvolumeclaim",
+    "pod",
+    "poddisruptionbudget",
+    "replicaset",
+    "resourcequota",
+    "role",
+    "rolebinding",
+    "secret",
+    "service",
+    "serviceaccount",
+    "statefulset",
+];
+
+// target package of a classified resource and the rule which decided it
+struct Placement {
+    package_name: String,
+    rule_idx: Option<usize>,
+}
+
+// rendered files of a package, patch stubs are scaffolded, files which
+// already exist belong to the user
+struct PackageFiles {
+    stubs: Vec<(PathBuf, String)>,
+    descriptor: (PathBuf, String),
+    extra: Vec<(PathBuf, String)>,
+}
+
+// a resource placed in a package, emitted once all resources are placed
+struct PlacedResource {
+    package_name: String,
+    rule_idx: Option<usize>,
+    kind: String,
+    name: String,
+    namespace: Option<String>,
+    filepath: PathBuf,
+    manifest: Yaml,
+    // upstream text of the document, see emitter::emit
+    source: Option<String>,
+    // group file the resource is appended to, None if it has its own file
+    group_idx: Option<usize>,
+    // apiVersion of the upstream manifest if it was upgraded
+    upgraded_from: Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Serialize, Default)]
+struct Package {
+    name: String,
+    spec: Option<String>,
+    #[serde(skip)]
+    rule_idx: Option<usize>,
+    resources: Vec<Resource>,
+    // distinct filenames of the resources, which share files when grouped
+    files: Vec<String>,
+    filename: Option<String>,
+    path: Option<String>,
+    // path of the package relative to the root or overlay descriptor
+    relativePath: Option<String>,
+    // descriptor apiVersion and kind, which differ for components
+    component: bool,
+    apiVersion: String,
+    kind: String,
+    // aggregates computed once all resources are classified
+    kinds: Vec<String>,
+    namespaces: Vec<String>,
+    // namespace shared by all namespaced resources of the package
+    commonNamespace: Option<String>,
+    // container images referenced by the resources of the package
+    images: Vec<String>,
+    // digests of the images pinned via the images transformer
+    digests: BTreeMap<String, String>,
+    // ConfigMaps and Secrets converted to generators instead of resources
+    configMapGenerator: Vec<generators::GeneratorEntry>,
+    secretGenerator: Vec<generators::GeneratorEntry>,
+    // patch files relative to the package descriptor
+    patches: Vec<String>,
+    // namePrefix and nameSuffix rendered for the paths of the first resource
+    #[serde(skip)]
+    name_affixes: Option<NameAffixes>,
+    resourceCount: usize,
+    byKind: BTreeMap<String, Vec<Resource>>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Serialize, PartialEq)]
+struct Resource {
+    index: u32,
+    // identifiers derived from the upstream kind, namespace and name, which
+    // unlike the index survive reordering, e.g. deployment-projectcontour-contour
+    id: String,
+    // first 12 hex digits of the sha256 of kind/namespace/name
+    idHash: String,
+    name: String,
+    kind: String,
+    namespace: Option<String>,
+    scope: Scope,
+    filename: Option<String>,
+    path: Option<String>,
+    // container images referenced by the resource
+    images: Vec<String>,
+    // the parsed manifest, e.g. resource.manifest.metadata.labels
+    manifest: tera::Value,
+}
+
+// command line options
+#[derive(Default)]
+pub struct Options {
+    pub command: Command,
+    pub config_path: String,
+    pub output_dir: Option<String>,
+    pub dry_run: bool,
+    pub diff: bool,
+    pub check: bool,
+    pub prune: bool,
+    pub yes: bool,
+    pub force: bool,
+    pub archive: Option<String>,
+    // snapshot directories the generated files are recorded into or
+    // compared with
+    pub record: Option<String>,
+    pub verify: Option<String>,
+    pub stdout: bool,
+    pub packages: Vec<String>,
+    pub report: Option<String>,
+    // entry of the Profiles section laid over the config
+    pub profile: Option<String>,
+    // config fields overridden on the command line, <path>=<value>
+    pub sets: Vec<String>,
+    // resources selected by explain
+    pub kind: Option<String>,
+    pub name: Option<String>,
+    // format of the list output
+    pub format: report::Format,
+    // manifest url the starter config of init is derived from
+    pub url: Option<String>,
+    // version bump sets, the latest release if unset, and the versions
+    // compared by diff-versions, from defaulting to Top.version
+    pub from: Option<String>,
+    pub to: Option<String>,
+    // generate the packages after bump
+    pub generate: bool,
+    // print a record per file instead of logging
+    pub porcelain: bool,
+    // collect failing resources and packages and write the others
+    pub keep_going: bool,
+    // fail resources with a deprecated apiVersion instead of warning
+    pub strict_deprecations: bool,
+    // upstreams fetched at once
+    pub jobs: usize,
+    // where downloading and writing show how far they got
+    pub progress: Progress,
+    // -1 only logs errors, 1 and 2 debug and trace messages
+    pub verbosity: i8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Command {
+    // write the packages
+    #[default]
+    Generate,
+    // print how the split rules classify the resources
+    Explain,
+    // print the packages and their resources
+    List,
+    // write a starter config
+    Init,
+    // check a config without fetching the upstream
+    Validate,
+    // print the JSON Schema of the config
+    Schema,
+    // upgrade a config to the current apiVersion
+    Migrate,
+    // update Top.version in the config
+    Bump,
+    // compare the packages of two upstream versions
+    DiffVersions,
+    // print candidate split rules for an upstream
+    SuggestRules,
+    // walk through the classification and add split rules interactively
+    Review,
+}
+
+// runs a command of the command line
+pub fn run(mut options: Options) -> Result<(), Error> {
+    if let (Command::Init, Some(url)) = (options.command, &options.url) {
+        return init(url, Path::new(&options.config_path), options.force);
+    }
+    if options.command == Command::Schema {
+        let schema = schemars::schema_for!(Config);
+        println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+        return Ok(());
+    }
+    if options.command == Command::Migrate {
+        return migrate(Path::new(&options.config_path));
+    }
+    if options.command == Command::Bump {
+        bump(&options)?;
+        if !options.generate {
+            return Ok(());
+        }
+        options.command = Command::Generate;
+    }
+    if options.command == Command::DiffVersions {
+        return diff_versions(&options);
+    }
+    if options.command == Command::Review {
+        return review(&options);
+    }
+    if options.command == Command::SuggestRules {
+        let source = match &options.url {
+            Some(url) => url.clone(),
+            None => Config::load(Path::new(&options.config_path), options.profile.as_deref(), &options.sets)?.render_source()?,
+        };
+        print!("{}", suggest::suggest_rules(&fetch_manifests(&source)?));
+        return Ok(());
+    }
+    if options.command == Command::Validate {
+        let config = Config::parse(Path::new(&options.config_path), options.profile.as_deref(), &options.sets)
+            .map_err(Error::Config)?;
+        // problems of the shared parts are reported once
+        let mut problems: Vec<String> = Vec::new();
+        for config in config.upstreams() {
+            for problem in validate::validate(&config) {
+                if !problems.contains(&problem) {
+                    problems.push(problem);
+                }
+            }
+        }
+        if !problems.is_empty() {
+            return Err(Error::Config(format!(
+                "{} is not valid:\n  {}",
+                options.config_path,
+                problems.join("\n  ")
+            )));
+        }
+        if options.verbosity >= 0 {
+            println!("{} is valid", options.config_path);
+        }
+        return Ok(());
+    }
+    let config = Config::load(Path::new(&options.config_path), options.profile.as_deref(), &options.sets)?;
+    let output_dir = options.output_dir.clone().or(config.Top.outputDir.clone());
+    let mut configs = config.upstreams();
+    let mode = if options.command != Command::Generate {
+        output::Mode::Inspect
+    } else if options.dry_run {
+        output::Mode::DryRun
+    } else if options.diff {
+        output::Mode::Diff
+    } else if options.check {
+        output::Mode::Check
+    } else if options.stdout {
+        output::Mode::Stdout
+    } else if options.archive.is_some() || options.record.is_some() || options.verify.is_some() {
+        output::Mode::Archive
+    } else {
+        output::Mode::Write
+    };
+    let mut output = output::Output::new(output_dir.as_deref(), mode, options.force, options.porcelain);
+
+    // yaml files of the previous run, read before they are overwritten
+    let previous: Vec<(PathBuf, String)> = match (&configs[0].Changelog, mode) {
+        (Some(_), output::Mode::Write | output::Mode::DryRun) => output
+            .recorded()
+            .iter()
+            .filter(|file| file.extension().is_some_and(|ext| ext == "yaml" || ext == "yml"))
+            .filter_map(|file| fs::read_to_string(file).ok().map(|content| (file.clone(), content)))
+            .collect(),
+        _ => Vec::new(),
+    };
+    let prune_keep = configs[0].PruneKeep.clone();
+    let mut runs = Vec::new();
+    let mut failures: Vec<Error> = Vec::new();
+    let generated = generate(&mut configs, &options, &mut output);
+    for (config, generated) in configs.iter().zip(generated) {
+        // an upstream which can not be fetched leaves the other upstreams
+        match generated {
+            Ok(Some(run)) => runs.push(run),
+            Ok(None) => {}
+            Err(err) if options.keep_going => {
+                let err = Error::context(&format!("upstream {}", config.Top.name))(err);
+                warn!("{}", err);
+                failures.push(err);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    if options.command == Command::Explain {
+        return Ok(());
+    }
+    let mut reports: Vec<report::Report> = Vec::new();
+    let mut packages: Vec<Package> = Vec::new();
+    let mut skipped: Vec<(u32, String)> = Vec::new();
+    let (mut dropped, mut warnings) = (0, 0);
+    for run in runs {
+        failures.extend(run.failures);
+        reports.push(run.report);
+        packages.extend(run.packages);
+        skipped.extend(run.skipped);
+        dropped += run.dropped;
+        warnings += run.warnings;
+    }
+    if options.command == Command::List {
+        print!("{}", report::render_all(&mut reports, options.format));
+        return Ok(());
+    }
+    if let Some(path) = configs[0].render_changelog_path()? {
+        if mode == output::Mode::Write || mode == output::Mode::DryRun {
+            let resource_paths: Vec<PathBuf> = reports
+                .iter()
+                .flat_map(report::Report::resources)
+                .map(|resource| PathBuf::from(&resource.path))
+                .collect();
+            // descriptors and other generated files are not resources
+            let previous_sources = previous
+                .iter()
+                .filter(|(file, _)| resource_paths.contains(file) || !output.files().contains(file))
+                .map(|(_, content)| content.as_str());
+            let prefix = configs[0].Provenance.as_ref().map(|provenance| provenance.annotationPrefix.as_str());
+            let previous_resources = changelog::resources(previous_sources, prefix);
+            let manifests = reports
+                .iter()
+                .flat_map(report::Report::resources)
+                .filter_map(|resource| resource.manifest.as_deref());
+            let current_resources = changelog::resources(manifests, prefix);
+            let title: Vec<String> = configs
+                .iter()
+                .map(|config| format!("{} {}", config.Top.name, config.Top.version))
+                .collect();
+            let content = changelog::render(&title.join(", "), &previous_resources, &current_resources);
+            output.write(Path::new(&path), content)?;
+        }
+    }
+    if mode == output::Mode::Write {
+        // the files of failed resources are missing from this run, not stale
+        if options.prune && !failures.is_empty() {
+            warn!("not pruning, {} failures left files out of this run", failures.len());
+        } else if options.prune {
+            output.prune(&prune_keep, options.yes)?;
+        }
+        output.save_record()?;
+        if !output.refused().is_empty() {
+            return Err(Error::Refused(output.refused().to_vec()));
+        }
+    }
+    if let Some(report_path) = &options.report {
+        report::write_all(&mut reports, Path::new(report_path)).map_err(Error::file("write", report_path))?;
+    }
+    if mode == output::Mode::Stdout {
+        for name in &options.packages {
+            if !packages.iter().any(|package| &package.name == name) {
+                return Err(Error::Usage(format!("unknown package {}", name)));
+            }
+        }
+        output.print_stream(&options.packages);
+    }
+    if let Some(archive) = &options.archive {
+        output.write_archive(Path::new(archive))?;
+    }
+    if let Some(dir) = &options.record {
+        // a snapshot missing the files of failed resources would not verify
+        if failures.is_empty() {
+            output.record_snapshot(Path::new(dir))?;
+        } else {
+            warn!("not recording the snapshot, {} failures left files out of this run", failures.len());
+        }
+    }
+    if let Some(dir) = &options.verify {
+        let differences = output.verify_snapshot(Path::new(dir))?;
+        if !differences.is_empty() {
+            return Err(Error::Drift {
+                what: format!("generated files differ from the snapshot in {}", dir),
+                differences,
+            });
+        }
+        if options.verbosity >= 0 {
+            println!("generated files match the snapshot in {}", dir);
+        }
+    }
+    if options.diff {
+        output.print_removed(&prune_keep);
+    }
+    if options.check {
+        let drift = output.drift(&prune_keep);
+        if !drift.is_empty() {
+            return Err(Error::Drift {
+                what: "generated files are out of date".to_string(),
+                differences: drift,
+            });
+        }
+        if options.verbosity >= 0 {
+            println!("generated files are up to date");
+        }
+    }
+
+    if options.porcelain {
+        for (document_idx, err) in &skipped {
+            // the first line of the error without the document it names
+            let reason = err.lines().next().unwrap_or_default();
+            let reason = reason.split_once(": ").map_or(reason, |(_, reason)| reason);
+            println!("SKIPPED doc#{} {}", document_idx, reason);
+        }
+    } else if !skipped.is_empty() {
+        let errors: Vec<&str> = skipped.iter().map(|(_, err)| err.as_str()).collect();
+        warn!("skipped {} documents:\n  {}", skipped.len(), errors.join("\n  "));
+    }
+    // the summary is left out of the records and when only errors are wanted
+    if mode != output::Mode::Diff
+        && mode != output::Mode::Check
+        && options.verify.is_none()
+        && !options.porcelain
+        && options.verbosity >= 0
+    {
+        let mut summary = summary(&packages, skipped.len(), dropped, warnings);
+        summary.push_str(&report::version_differences(&reports));
+        // the yaml stream owns stdout
+        if mode == output::Mode::Stdout {
+            eprint!("{}", summary);
+        } else {
+            print!("{}", summary);
+        }
+    }
+    if !failures.is_empty() {
+        return Err(Error::Failures(failures));
+    }
+    Ok(())
+}
+
+// packages, problems and report of generating the packages of one upstream
+struct Run {
+    packages: Vec<Package>,
+    // documents which were skipped and why
+    skipped: Vec<(u32, String)>,
+    // errors collected under --keep-going
+    failures: Vec<Error>,
+    dropped: usize,
+    warnings: usize,
+    report: report::Report,
+}
+
+// collects the error of a resource or package under --keep-going, so the
+// others are still written, and returns it otherwise
+fn keep_going(options: &Options, failures: &mut Vec<Error>, result: Result<(), Error>) -> Result<(), Error> {
+    match result {
+        Err(err) if options.keep_going => {
+            warn!("{}", err);
+            failures.push(err);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+// a document which is no resource is skipped unless StrictDocuments is set
+fn skip_document(config: &Config, skipped: &mut Vec<(u32, String)>, document_idx: u32, err: String) -> Result<(), Error> {
+    if config.StrictDocuments {
+        return Err(Error::Document(format!("invalid document, {}", err)));
+    }
+    warn!("skip {}", err);
+    skipped.push((document_idx, err));
+    Ok(())
+}
+
+// fetches the upstream manifests of the configs concurrently and writes
+// their packages one after the other, None in explain mode, one result per
+// config
+fn generate(
+    configs: &mut [Config],
+    options: &Options,
+    output: &mut output::Output,
+) -> Vec<Result<Option<Run>, Error>> {
+    let sources: Vec<Result<String, Error>> = configs
+        .iter_mut()
+        .map(|config| {
+            config.check_package_specs()?;
+            let source = config.render_source()?;
+            config.Top.source = Some(source.clone());
+            Ok(source)
+        })
+        .collect();
+    let urls: Vec<String> = sources.iter().filter_map(|source| source.as_ref().ok().cloned()).collect();
+    let mut downloads = fetch::fetch_all(&urls, options.jobs, &options.progress).into_iter();
+    configs
+        .iter()
+        .zip(sources)
+        .map(|(config, source)| {
+            source?;
+            let (body, charset) = downloads.next().expect("one download per source")?;
+            split(config, options, output, BufReader::new(body), charset.as_deref())
+        })
+        .collect()
+}
+
+/// What [`split_manifests`] generated for an upstream.
+pub struct SplitResult {
+    /// Rendered path and content of every generated file, e.g. resources,
+    /// package descriptors and scaffolded patches.
+    pub files: Vec<(PathBuf, Vec<u8>)>,
+    /// Names of the packages.
+    pub packages: Vec<String>,
+    /// Index and problem of the documents which were skipped.
+    pub skipped: Vec<(u32, String)>,
+    /// Resources not placed in any package.
+    pub dropped: usize,
+    /// Problems which did not stop the split, e.g. failing patches.
+    pub warnings: usize,
+}
+
+/// Splits the upstream manifests read from `reader` into the packages of
+/// `config` and renders their files in memory instead of fetching the
+/// upstream and writing the files. A config listing `Upstreams` is split
+/// per entry of [`Config::upstreams`]. A config deserialized instead of
+/// loaded with [`Config::load`] takes `Templates.dir` relative to the
+/// working directory.
+pub fn split_manifests<R: Read>(reader: R, config: &Config) -> Result<SplitResult, Error> {
+    config.check_package_specs()?;
+    let mut config = config.clone();
+    if !config.prepared {
+        config.prepare_templates().map_err(Error::Config)?;
+    }
+    config.Top.source = Some(config.render_source()?);
+    let mut output = output::Output::in_memory();
+    let run = split(&config, &Options::default(), &mut output, BufReader::new(reader), None)?
+        .expect("split only returns None in explain mode");
+    Ok(SplitResult {
+        files: output.into_files(),
+        packages: run.packages.into_iter().map(|package| package.name).collect(),
+        skipped: run.skipped,
+        dropped: run.dropped,
+        warnings: run.warnings,
+    })
+}
+
+// writes the packages of the upstream manifests of a config, which are read
+// one document at a time, None in explain mode
+fn split<R: BufRead>(
+    config: &Config,
+    options: &Options,
+    output: &mut output::Output,
+    mut reader: R,
+    charset: Option<&str>,
+) -> Result<Option<Run>, Error> {
+    let mut resource_idx = 0u32;
+    let mut packages: HashMap<String, Package> = HashMap::new();
+    let upstream = config.Top.source.clone().unwrap_or_default();
+    // the mirror is a copy of the whole source
+    let mut mirrored = Vec::new();
+    let reader: Box<dyn BufRead + '_> = match config.render_mirror_path()? {
+        Some(mirror_path) => {
+            reader.read_to_end(&mut mirrored).map_err(Error::file("read", &upstream))?;
+            output.write(Path::new(&mirror_path), &mirrored)?;
+            Box::new(&mirrored[..])
+        }
+        None => Box::new(reader),
+    };
+
+    // the http client is only built if images are pinned
+    let mut digest_resolver = config.PinImageDigests.as_ref().map(|_| images::DigestResolver::default());
+    let mut schema_validator = schemas::SchemaValidator::new(config.KubernetesVersion.as_deref());
+    let mut skipped: Vec<(u32, String)> = Vec::new();
+    // resources and packages which failed under --keep-going
+    let mut failures: Vec<Error> = Vec::new();
+    // resources not placed in any package and problems which did not stop the run
+    let mut dropped = 0;
+    let mut warnings = 0;
+    // written resource files and the resource in them
+    let mut written: HashMap<PathBuf, String> = HashMap::new();
+    let mut groups: Vec<(PathBuf, String)> = Vec::new();
+    let mut placed_resources: Vec<PlacedResource> = Vec::new();
+    let mut report = report::Report::new(
+        config.Top.name.clone(),
+        config.Top.source.clone().unwrap_or_default(), config.Top.version.clone());
+    let generated_at = humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string();
+    // the written manifests are only compared by the changelog and
+    // diff-versions, the report keeps their digests otherwise
+    let keep_manifests = config.Changelog.is_some() || options.command == Command::DiffVersions;
+    // documents which fail to parse, skipped before the resources
+    let mut broken: Vec<(u32, String)> = Vec::new();
+    let mut merged = 0;
+    //merge keys and expand kind List documents into their items
+    let documents = manifest::read_manifests(reader, charset)
+        .enumerate()
+        .flat_map(|(document_idx, document)| -> Vec<Result<manifest::Document, Error>> {
+            let document = match document {
+                Ok(Ok(document)) => document,
+                Ok(Err(err)) if config.LenientParsing => {
+                    warn!("skip {}", err);
+                    broken.push((document_idx as u32, err));
+                    return Vec::new();
+                }
+                Ok(Err(err)) => return vec![Err(Error::Document(format!("unable to parse {}", err)))],
+                Err(err) => return vec![Err(Error::file("read", &upstream)(err))],
+            };
+            merged += 1;
+            match merge_keys(document.yaml) {
+                Ok(yaml) => manifest::expand_list(manifest::Document { yaml, ..document })
+                    .into_iter()
+                    .map(Ok)
+                    .collect(),
+                Err(err) => vec![Err(Error::Document(format!(
+                    "document {}: unable to merge keys: {}",
+                    merged - 1,
+                    err
+                )))],
+            }
+        });
+    let documents: Box<dyn Iterator<Item = Result<manifest::Document, Error>> + '_> = match config.CRDsFirst {
+        //move namespaces and CRDs to the front so they are numbered first,
+        //which needs all documents at once
+        true => {
+            let mut documents = documents.collect::<Result<Vec<manifest::Document>, Error>>()?;
+            documents.sort_by_key(|document| apply_order(document.yaml["kind"].as_str().unwrap_or_default()));
+            Box::new(documents.into_iter().map(Ok))
+        }
+        false => Box::new(documents),
+    };
+    let kubernetes_version = config.KubernetesVersion.as_deref().and_then(deprecations::parse_version);
+    let processed = options.progress.count(&format!("split {}", config.Top.name), "resources", None);
+    for (document_idx, document) in documents.enumerate() {
+        let document_idx = document_idx as u32;
+        let document = document?;
+        let mut manifest = document.yaml;
+
+        //drop noisy server side fields
+        if let Some(strip_fields) = &config.StripFields {
+            for field in &strip_fields.fields {
+                manifest::remove_field(&mut manifest, field);
+            }
+        }
+        if config.StripHelmMetadata {
+            manifest::strip_helm_metadata(&mut manifest);
+        }
+
+        //apply configured patches before the resource metadata is taken, a
+        //patch which fails loses the resource instead of writing it unpatched
+        if !config.Patches.is_empty() {
+            let resource = match Resource::from_manifest(&manifest, document_idx) {
+                Ok(Some(resource)) => resource,
+                Ok(None) => continue,
+                Err(err) => {
+                    skip_document(config, &mut skipped, document_idx, err)?;
+                    continue;
+                }
+            };
+            let patched = config
+                .Patches
+                .iter()
+                .enumerate()
+                .filter(|(_, rule)| rule.matcher.do_match(&resource))
+                .try_for_each(|(patch_idx, rule)| {
+                    rule.apply(&mut manifest)
+                        .map_err(|err| Error::Document(format!("unable to apply Patches[{}]: {}", patch_idx, err)))
+                });
+            if let Err(err) = patched {
+                let description = format!(
+                    "{} {}/{}",
+                    resource.kind,
+                    resource.namespace.clone().unwrap_or_default(),
+                    resource.name
+                );
+                keep_going(options, &mut failures, Err(Error::context(&description)(err)))?;
+                continue;
+            }
+        }
+
+        //pin images to digests in the manifest itself if requested
+        if let (Some(pinning), Some(digest_resolver)) = (&config.PinImageDigests, &mut digest_resolver) {
+            if pinning.rewriteManifests {
+                let digests = digest_resolver.resolve_all(&images::find_images(&manifest));
+                images::pin_images(&mut manifest, &digests);
+            }
+        }
+
+        //rewrite deprecated apiVersions with a mechanical conversion
+        let upgraded_from = match config.UpgradeApiVersions {
+            true => deprecations::upgrade(&mut manifest, kubernetes_version),
+            false => None,
+        };
+
+        //get resource metadata
+        let mut resource = match Resource::from_manifest(&manifest, document_idx) {
+            Ok(Some(resource)) => resource,
+            Ok(None) => continue,
+            Err(err) => {
+                skip_document(config, &mut skipped, document_idx, err)?;
+                continue;
+            }
+        };
+        processed.inc(1);
+
+        //classify resource and store a copy of it in every target package
+        if options.command == Command::Explain {
+            let kind = options.kind.as_ref().map(|kind| kind.to_lowercase());
+            let name = options.name.as_ref().map(|name| name.to_lowercase());
+            if kind.is_none_or(|kind| kind == resource.kind.to_lowercase())
+                && name.is_none_or(|name| name == resource.name.to_lowercase())
+            {
+                println!(
+                    "{} {}/{} (document {})",
+                    resource.kind,
+                    resource.namespace.clone().unwrap_or_default(),
+                    resource.name,
+                    document_idx
+                );
+                for line in config.explain(&resource) {
+                    println!("  {}", line);
+                }
+            }
+            continue;
+        }
+
+        let description = format!(
+            "{} {}/{}",
+            resource.kind,
+            resource.namespace.clone().unwrap_or_default(),
+            resource.name
+        );
+
+        if let Some(upgraded_from) = &upgraded_from {
+            info!(
+                "upgrade {} from {} to {}",
+                description,
+                upgraded_from,
+                manifest["apiVersion"].as_str().unwrap_or_default()
+            );
+        }
+
+        //report apiVersions the target kubernetes version deprecated
+        let api_version = manifest["apiVersion"].as_str().unwrap_or_default();
+        if let Some(deprecation) = deprecations::find(api_version, &resource.kind, kubernetes_version) {
+            let deprecated = deprecation.describe(&resource.kind, kubernetes_version);
+            if options.strict_deprecations {
+                keep_going(options, &mut failures, Err(Error::context(&description)(Error::Document(deprecated))))?;
+                continue;
+            }
+            warn!("{}: {}", description, deprecated);
+            warnings += 1;
+        }
+
+        let placements = match config.classify(&resource).map_err(Error::context(&description)) {
+            Ok(placements) => placements,
+            Err(err) => {
+                keep_going(options, &mut failures, Err(err))?;
+                continue;
+            }
+        };
+        if placements.is_empty() {
+            dropped += 1;
+            debug!("drop {}, no package", description);
+        }
+        for placement in &placements {
+            match placement.rule_idx {
+                Some(rule_idx) => debug!("place {} in {} by SplitRules[{}]", description, placement.package_name, rule_idx),
+                None => debug!("place {} in the default package {}", description, placement.package_name),
+            }
+        }
+        for line in config.explain(&resource) {
+            trace!("{}: {}", description, line);
+        }
+
+        //number only the resources which are written
+        if config.IndexMode == IndexMode::Resource {
+            if placements.is_empty() {
+                continue;
+            }
+            resource.index = resource_idx;
+            resource_idx += 1;
+        }
+
+        // a failing placement only loses this resource under --keep-going
+        let source = document.source;
+        let placed = (|| -> Result<(), Error> {
+            for placement in placements {
+                let package_name = placement.package_name;
+                let mut resource = resource.clone();
+                let mut manifest = manifest.clone();
+
+                //move namespaced resources of the rule into another namespace
+                let set_namespace = placement
+                    .rule_idx
+                    .and_then(|rule_idx| config.SplitRules[rule_idx].setNamespace.as_ref());
+                if let (Some(namespace), Scope::Namespaced) = (set_namespace, resource.scope) {
+                    manifest::set_metadata_field(&mut manifest, "namespace", namespace);
+                    resource.namespace = Some(namespace.clone());
+                    resource.manifest = yaml_to_value(&manifest);
+                }
+
+                let rule_idx = placement.rule_idx;
+                let spec = rule_idx.and_then(|rule_idx| config.SplitRules[rule_idx].packageSpec.clone());
+                let package = packages.entry(package_name.clone()).or_insert_with(|| Package {
+                    name: package_name.clone(),
+                    spec: spec.clone(),
+                    rule_idx,
+                    ..Default::default()
+                });
+                //templated package names are only known now and the resources
+                //placed by default give their package the default spec
+                if package.spec != spec {
+                    let given_by = |rule_idx: Option<usize>| match rule_idx {
+                        Some(rule_idx) => format!("SplitRules[{}]", rule_idx),
+                        None => "the default placement".to_string(),
+                    };
+                    return Err(Error::Config(format!(
+                        "{}: package {} gets package spec {} but {} gives it {}",
+                        given_by(rule_idx),
+                        package_name,
+                        spec.as_deref().unwrap_or("DefaultPackageSpec"),
+                        given_by(package.rule_idx),
+                        package.spec.as_deref().unwrap_or("DefaultPackageSpec"),
+                    )));
+                }
+                //rename the resource
+                let rename = placement
+                    .rule_idx
+                    .and_then(|rule_idx| config.SplitRules[rule_idx].rename.as_ref().map(|rename| (rule_idx, rename)));
+                if let Some((rule_idx, rename)) = rename {
+                    let name = config
+                        .render_resource_name(rule_idx, rename, package, &resource)
+                        .map_err(Error::context(&description))?;
+                    manifest::set_metadata_field(&mut manifest, "name", &name);
+                    let key = format!("{}original-name", ANNOTATION_PREFIX);
+                    manifest::set_metadata_entry(&mut manifest, "annotations", &key, &resource.name);
+                    resource.name = name;
+                    resource.manifest = yaml_to_value(&manifest);
+                }
+
+                //add the labels and annotations of the rule
+                if let Some(rule_idx) = placement.rule_idx {
+                    let (labels, annotations) = config
+                        .render_rule_metadata(rule_idx, package, &resource)
+                        .map_err(Error::context(&description))?;
+                    for (key, value) in &labels {
+                        manifest::set_metadata_entry(&mut manifest, "labels", key, value);
+                    }
+                    for (key, value) in &annotations {
+                        manifest::set_metadata_entry(&mut manifest, "annotations", key, value);
+                    }
+                    if !labels.is_empty() || !annotations.is_empty() {
+                        resource.manifest = yaml_to_value(&manifest);
+                    }
+                }
+
+                //check the manifest against the kubernetes schemas
+                if let Some(validation) = &config.SchemaValidation {
+                    let problems = schema_validator
+                        .validate(validation, &resource.manifest)
+                        .map_err(Error::Config)?;
+                    if !problems.is_empty() {
+                        let invalid = format!("invalid manifest:\n  {}", problems.join("\n  "));
+                        match validation.onInvalid {
+                            schemas::OnInvalid::Fail => return Err(Error::context(&description)(Error::Document(invalid))),
+                            schemas::OnInvalid::Warn => {
+                                warn!("{}: {}", description, invalid);
+                                warnings += 1;
+                            }
+                        }
+                    }
+                }
+
+                //convert ConfigMaps and Secrets to generators with data files
+                if let Some(rule_idx) = config.generator_rule(&resource) {
+                    let package_path = config.render_package_path(package)?;
+                    let dir = config
+                        .render_generator_dir(rule_idx, package, &resource)
+                        .map_err(Error::context(&description))?;
+                    let rule = &config.Generators[rule_idx];
+                    if let Some((entry, files)) = generators::to_generator(rule, &manifest, &dir) {
+                        for (filename, content) in files {
+                            output.write(&Path::new(&package_path).join(filename), content)?;
+                        }
+                        if resource.kind == "Secret" {
+                            package.secretGenerator.push(entry);
+                        } else {
+                            package.configMapGenerator.push(entry);
+                        }
+                        continue;
+                    }
+                }
+
+                //restart the index within the package or kind if requested
+                match config.resource_spec(package, placement.rule_idx).1.indexScope {
+                    IndexScope::Global => {}
+                    IndexScope::Package => resource.index = package.resources.len() as u32,
+                    IndexScope::Kind => {
+                        let same_kind = package.resources.iter().filter(|r| r.kind == resource.kind);
+                        resource.index = same_kind.count() as u32;
+                    }
+                }
+
+                if package.name_affixes.is_none() {
+                    let name_affixes = config.render_name_affixes(package).map_err(Error::context(&description))?;
+                    package.name_affixes = Some(name_affixes);
+                }
+                let mut rendered_filename = config
+                    .render_resource_filename(package, &resource, placement.rule_idx)
+                    .map_err(Error::context(&description))?;
+                let mut pathname = config
+                    .render_resource_path(package, &resource, placement.rule_idx)
+                    .map_err(Error::context(&description))?;
+                if let Some(sanitize) = &config.Sanitize {
+                    rendered_filename = filters::sanitize_path(&rendered_filename, sanitize.maxLength);
+                    pathname = filters::sanitize_path(&pathname, sanitize.maxLength);
+                }
+                //a resource name must not move the file out of its directory
+                let escapes = Path::new(&rendered_filename)
+                    .components()
+                    .any(|c| !matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir));
+                if escapes {
+                    return Err(Error::Layout(format!(
+                        "filename {} of {} {} leaves the resource path {}",
+                        rendered_filename, resource.kind, resource.name, pathname
+                    )));
+                }
+                //nor may a name rendered into the resource path leave the
+                //output directory
+                let filepath = Path::new(&pathname).join(&rendered_filename);
+                if leaves_output_dir(Path::new(&pathname)) || leaves_output_dir(&filepath) {
+                    return Err(Error::Layout(format!(
+                        "path {} of {} {} leaves the output directory",
+                        filepath.display(),
+                        resource.kind,
+                        resource.name
+                    )));
+                }
+                if cfg!(windows) || config.PortablePaths {
+                    if let Err(err) = check_portable(&filepath) {
+                        return Err(Error::Layout(format!(
+                            "path of {} {} is not valid on Windows: {}",
+                            resource.kind, resource.name, err
+                        )));
+                    }
+                }
+                let mut filename = rendered_filename.clone();
+
+                //two resources must not be written to the same file
+                let description = format!(
+                    "{} {}/{}",
+                    resource.kind,
+                    resource.namespace.clone().unwrap_or_default(),
+                    resource.name
+                );
+                let grouped = config.resource_spec(package, placement.rule_idx).1.groupBy != GroupBy::None;
+                // grouped resources share the file of their group
+                let mut group_idx;
+                let mut dedup = 0;
+                loop {
+                    let filepath = Path::new(&pathname).join(&filename);
+                    group_idx = groups.iter().position(|(path, _)| path == &filepath);
+                    let other = match written.get(&filepath) {
+                        Some(other) if !(grouped && group_idx.is_some()) => other,
+                        _ => break,
+                    };
+                    if config.FilenameCollisions == Collisions::Fail {
+                        return Err(Error::Layout(format!(
+                            "{} and {} are both written to {}, make the filename template unique or set FilenameCollisions: suffix",
+                            other,
+                            description,
+                            filepath.display()
+                        )));
+                    }
+                    dedup += 1;
+                    filename = dedup_filename(&rendered_filename, dedup);
+                }
+                debug!("write {} to {}", description, Path::new(&pathname).join(&filename).display());
+                written
+                    .entry(Path::new(&pathname).join(&filename))
+                    .or_insert(description);
+
+                resource.filename = Some(filename.clone());
+                resource.path = Some(pathname.clone());
+                let filepath = Path::new(&pathname).join(&filename);
+                let (kind, name, namespace) = (resource.kind.clone(), resource.name.clone(), resource.namespace.clone());
+
+                package.resources.push(resource);
+
+                if config.package_spec(package).1.stampCommonMetadata {
+                    let (labels, annotations) = config.render_common_metadata(package)?;
+                    for (key, value) in &labels {
+                        manifest::set_metadata_entry(&mut manifest, "labels", key, value);
+                    }
+                    for (key, value) in &annotations {
+                        manifest::set_metadata_entry(&mut manifest, "annotations", key, value);
+                    }
+                }
+
+                //emit and write the resource once all resources are placed
+                let group_idx = match group_idx {
+                    _ if !grouped => None,
+                    Some(group_idx) => Some(group_idx),
+                    None => {
+                        groups.push((filepath.clone(), String::new()));
+                        Some(groups.len() - 1)
+                    }
+                };
+                placed_resources.push(PlacedResource {
+                    package_name: package.name.clone(),
+                    rule_idx: placement.rule_idx,
+                    kind,
+                    name,
+                    namespace,
+                    filepath,
+                    manifest,
+                    source: source.clone(),
+                    group_idx,
+                    upgraded_from: upgraded_from.clone(),
+                });
+            }
+            Ok(())
+        })();
+        keep_going(options, &mut failures, placed)?;
+
+        // the placed resources are emitted and written in batches to keep
+        // large upstreams out of memory
+        if placed_resources.len() >= EMIT_BATCH {
+            let placed_resources = std::mem::take(&mut placed_resources);
+            let files = emit_placed(config, placed_resources, &generated_at, keep_manifests, &mut report, output, &mut groups);
+            for result in output.write_all(&files, &options.progress) {
+                keep_going(options, &mut failures, result)?;
+            }
+        }
+    }
+    drop(processed);
+    if options.command == Command::Explain {
+        return Ok(None);
+    }
+    // parse errors come first as the documents were parsed up front before
+    broken.extend(skipped);
+    let skipped = broken;
+    let mut files = emit_placed(config, placed_resources, &generated_at, keep_manifests, &mut report, output, &mut groups);
+    files.extend(groups);
+    for result in output.write_all(&files, &options.progress) {
+        keep_going(options, &mut failures, result)?;
+    }
+    let mut packages: Vec<Package> = packages.into_values().collect();
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+    for package in packages.iter_mut() {
+        package.sort_resources(config.package_spec(package).1.sortBy, config.CRDsFirst);
+        package.aggregate();
+        package.set_component(config.package_spec(package).1.component);
+        if let (Some(pinning), Some(digest_resolver)) = (&config.PinImageDigests, &mut digest_resolver) {
+            if !pinning.rewriteManifests {
+                package.digests = digest_resolver.resolve_all(&package.images);
+            }
+        }
+    }
+
+    // write package descriptor for each package, they are rendered in
+    // parallel and written in the order of the packages
+    let rendered: Vec<Result<PackageFiles, Error>> = packages
+        .par_iter_mut()
+        .map(|package| {
+            let context = format!("package {}", package.name);
+            config.render_package_files(package).map_err(Error::context(&context))
+        })
+        .collect();
+    for (package, rendered) in packages.iter().zip(rendered) {
+        // a failing package keeps its resource files under --keep-going
+        let described = rendered.and_then(|files| {
+            for (filepath, content) in files.stubs {
+                output.scaffold(&filepath, content)?;
+            }
+            let (filepath, content) = files.descriptor;
+            report.set_descriptor(&package.name, &output.path(&filepath));
+            output.write(&filepath, content)?;
+            for (filepath, content) in files.extra {
+                output.write(&filepath, content)?;
+            }
+            Ok(())
+        });
+        keep_going(options, &mut failures, described)?;
+    }
+    let run = Run {
+        packages: Vec::new(),
+        failures: Vec::new(),
+        skipped,
+        dropped,
+        warnings: warnings + digest_resolver.map_or(0, |digest_resolver| digest_resolver.failures),
+        report,
+    };
+    if options.command == Command::List {
+        return Ok(Some(Run { packages, failures, ..run }));
+    }
+
+    // write root descriptor referencing all packages
+    if let Some(root_spec) = &config.RootSpec {
+        let root = (|| -> Result<(), Error> {
+            let pathname = config.render_root_path(root_spec)?;
+            let filename = config.render_root_filename(root_spec)?;
+            for package in packages.iter_mut() {
+                let package_path = Path::new(package.path.as_deref().unwrap_or_default());
+                package.relativePath = Some(relative_path(Path::new(&pathname), package_path));
+            }
+            let root_yaml = config.render_root_descriptor(root_spec, &packages)?;
+            output.write(&Path::new(&pathname).join(filename), root_yaml)
+        })();
+        keep_going(options, &mut failures, root)?;
+    }
+
+    // write gitops objects syncing the packages
+    let gitops = (|| -> Result<(), Error> {
+        let mut gitops_files = config.render_flux(&packages)?;
+        gitops_files.extend(config.render_argocd(&packages)?);
+        for (filepath, content) in gitops_files {
+            output.write(&filepath, content)?;
+        }
+        Ok(())
+    })();
+    keep_going(options, &mut failures, gitops)?;
+
+    // scaffold overlays, files which already exist belong to the user
+    let mut overlay_names: Vec<&String> = config.Overlays.keys().collect();
+    overlay_names.sort();
+    for overlay_name in overlay_names {
+        let overlay = (|| -> Result<(), Error> {
+            for (filepath, content) in config.render_overlay(overlay_name, &packages)? {
+                output.scaffold(&filepath, content)?;
+            }
+            Ok(())
+        })();
+        keep_going(options, &mut failures, overlay)?;
+    }
+
+    if options.dry_run {
+        let package_paths: Vec<(String, PathBuf)> = packages
+            .iter()
+            .map(|package| (package.name.clone(), PathBuf::from(package.path.clone().unwrap_or_default())))
+            .collect();
+        output.print_plan(&package_paths);
+    }
+    Ok(Some(Run { packages, failures, ..run }))
+}
+
+// emitting dominates for large upstreams, the resources are emitted in
+// parallel and recorded in the order they were placed, returns the files of
+// the resources which are not grouped
+fn emit_placed(
+    config: &Config,
+    placed_resources: Vec<PlacedResource>,
+    generated_at: &str,
+    keep_manifests: bool,
+    report: &mut report::Report,
+    output: &mut output::Output,
+    groups: &mut [(PathBuf, String)],
+) -> Vec<(PathBuf, String)> {
+    let emitted: Vec<(String, String)> = placed_resources
+        .par_iter()
+        .map(|placed| config.emit_resource(placed, generated_at))
+        .collect();
+    let mut files = Vec::new();
+    for (placed, (out_str, unstamped)) in placed_resources.into_iter().zip(emitted) {
+        let entry = report::ResourceEntry {
+            kind: placed.kind,
+            name: placed.name,
+            namespace: placed.namespace,
+            splitRule: placed.rule_idx,
+            path: output.path(&placed.filepath).display().to_string(),
+            upgradedFrom: placed.upgraded_from,
+            sha256: format!("{:x}", Sha256::digest(out_str.as_bytes())),
+            manifest: keep_manifests.then_some(unstamped),
+        };
+        report.add_resource(&placed.package_name, entry);
+        output.stream(&placed.package_name, &out_str);
+        match placed.group_idx {
+            None => files.push((placed.filepath, out_str)),
+            Some(group_idx) => {
+                let group = &mut groups[group_idx].1;
+                if !group.is_empty() && !out_str.starts_with("---") {
+                    group.push_str("---\n");
+                }
+                group.push_str(&out_str);
+            }
+        }
+    }
+    files
+}
+
+// rendered namePrefix and nameSuffix of a package
+type NameAffixes = (Option<String>, Option<String>);
+
+// rendered labels and annotations
+type RenderedMetadata = (BTreeMap<String, String>, BTreeMap<String, String>);
+
+// deserializes a yaml, toml or json document depending on the extension
+fn deserialize<T: serde::de::DeserializeOwned>(path: &Path, text: &str) -> Result<T, String> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(text).map_err(|err| err.to_string()),
+        Some("json") => serde_json::from_str(text).map_err(|err| err.to_string()),
+        _ => serde_yaml::from_str(text).map_err(|err| err.to_string()),
+    }
+}
+
+impl Config {
+    /// Reads a yaml, toml or json config, `profile` and `sets` are applied
+    /// as by `--profile` and `--set` on the command line.
+    pub fn load(config_path: &Path, profile: Option<&str>, sets: &[String]) -> Result<Config, Error> {
+        let config = Config::parse(config_path, profile, sets).map_err(Error::Config)?;
+        if config.apiVersion.is_none() {
+            warn!(
+                "{} has no apiVersion, run kustomize-upstream migrate to upgrade it to {}",
+                config_path.display(),
+                migrate::API_VERSION
+            );
+        }
+        Ok(config)
+    }
+
+    // reads and deserializes a yaml, toml or json config depending on the
+    // extension, errors name the location in the file, then environment
+    // variables are expanded and the profile and the fields set on the command
+    // line replace the ones of the file
+    fn parse(config_path: &Path, profile: Option<&str>, sets: &[String]) -> Result<Config, String> {
+        let config_text = fs::read_to_string(config_path)
+            .map_err(|err| format!("unable to read {}: {}", config_path.display(), err))?;
+        let mut value = match includes::compose(config_path, &config_text)? {
+            Some(value) => value,
+            None => {
+                let config: Config = deserialize(config_path, &config_text)
+                    .map_err(|err| format!("{}: {}", config_path.display(), err))?;
+                let mut value = serde_json::to_value(&config).map_err(|err| err.to_string())?;
+                interpolate::expand_env(&mut value).map_err(|err| format!("{}: {}", config_path.display(), err))?;
+                value
+            }
+        };
+        if let Some(profile) = profile {
+            overrides::apply_profile(&mut value, profile).map_err(|err| format!("{}: {}", config_path.display(), err))?;
+        }
+        let mut config: Config =
+            serde_json::from_value(value).map_err(|err| format!("{}: {}", config_path.display(), err))?;
+        if !sets.is_empty() {
+            // overrides are applied to the generic value of the parsed config
+            let mut value = serde_json::to_value(&config).map_err(|err| err.to_string())?;
+            for set in sets {
+                overrides::apply(&mut value, set)?;
+            }
+            config = serde_json::from_value(value).map_err(|err| format!("--set: {}", err))?;
+            let value = serde_json::to_value(&config).map_err(|err| err.to_string())?;
+            for set in sets {
+                overrides::check(&value, set)?;
+            }
+        }
+        let tops: Vec<(String, &Top)> = match config.Upstreams.is_empty() {
+            true => vec![("Top".to_string(), &config.Top)],
+            false => config
+                .Upstreams
+                .iter()
+                .enumerate()
+                .map(|(idx, upstream)| (format!("Upstreams[{}].Top", idx), &upstream.Top))
+                .collect(),
+        };
+        for (field, top) in tops {
+            for (name, value) in [("name", &top.name), ("version", &top.version), ("sourceTemplate", &top.sourceTemplate)] {
+                if value.is_empty() && (name != "version" || top.versions.is_empty()) {
+                    return Err(format!("{}: {} is missing {}", config_path.display(), field, name));
+                }
+            }
+        }
+        match config.apiVersion.as_deref() {
+            None | Some(migrate::API_VERSION) => {}
+            Some(migrate::LEGACY_API_VERSION) => {
+                return Err(format!(
+                    "{}: apiVersion {} is outdated, run kustomize-upstream migrate",
+                    config_path.display(),
+                    migrate::LEGACY_API_VERSION
+                ))
+            }
+            Some(version) => {
+                return Err(format!(
+                    "{}: apiVersion {} is not supported, expected {}",
+                    config_path.display(),
+                    version,
+                    migrate::API_VERSION
+                ))
+            }
+        }
+
+        // template files are resolved relative to the config file
+        let base_dir = config_path.parent().unwrap_or_else(|| Path::new(""));
+        config.DefaultPackageSpec.spec.load_template_files(base_dir)?;
+        for spec in config.PackageSpecs.values_mut() {
+            spec.load_template_files(base_dir)?;
+        }
+        for upstream in config.Upstreams.iter_mut() {
+            if let Some(default_spec) = &mut upstream.DefaultPackageSpec {
+                default_spec.spec.load_template_files(base_dir)?;
+            }
+            for spec in upstream.PackageSpecs.values_mut() {
+                spec.load_template_files(base_dir)?;
+            }
+        }
+        for rule in config.SplitRules.iter_mut() {
+            if let Some(resource_spec) = &mut rule.resourceSpec {
+                resource_spec.load_template_files(base_dir)?;
+            }
+        }
+        if let Some(root_spec) = &mut config.RootSpec {
+            root_spec.load_template_files(base_dir)?;
+        }
+        for overlay in config.Overlays.values_mut() {
+            load_template_file(base_dir, &overlay.templateFile, &mut overlay.template)?;
+            for patch in overlay.patches.iter_mut() {
+                load_template_file(base_dir, &patch.templateFile, &mut patch.template)?;
+            }
+        }
+        if let Some(validation) = &mut config.SchemaValidation {
+            validation.resolve_dirs(base_dir);
+        }
+        if let Some(problem) = validate::check_kubernetes_version(&config) {
+            return Err(format!("{}: {}", config_path.display(), problem));
+        }
+
+        config.base_dir = base_dir.to_path_buf();
+        config.prepare_templates()?;
+        Ok(config)
+    }
+
+    // sets up tera with the filters and compiles the templates of the config
+    fn prepare_templates(&mut self) -> Result<(), String> {
+        self.tera = self.template_environment()?;
+        self.compile_templates();
+        self.scripts = Arc::new(ClassifyScripts::compile(&self.SplitRules)?);
+        self.prepared = true;
+        Ok(())
+    }
+
+    // the templates of Templates.dir and the custom filters
+    fn template_environment(&self) -> Result<Tera, String> {
+        let mut tera = match &self.Templates {
+            Some(templates) => {
+                // globs take forward slashes on every platform
+                let glob = self.base_dir.join(&templates.dir).join("**").join("*");
+                Tera::new(&glob.to_string_lossy().replace('\\', "/"))
+                    .map_err(|err| format!("Templates.dir {}: {:?}", templates.dir, err))?
+            }
+            None => Tera::default(),
+        };
+        filters::register(&mut tera);
+        Ok(tera)
+    }
+
+    /// The config with every template parsed on each render, as before they
+    /// were compiled once, to benchmark the compilation.
+    #[doc(hidden)]
+    pub fn without_compiled_templates(&self) -> Result<Config, Error> {
+        let mut config = self.clone();
+        config.tera = config.template_environment().map_err(Error::Config)?;
+        config.compiled.clear();
+        Ok(config)
+    }
+
+    /// One config per upstream and version, the config itself if it lists
+    /// neither upstreams nor versions.
+    pub fn upstreams(mut self) -> Vec<Config> {
+        let upstreams = std::mem::take(&mut self.Upstreams);
+        let configs = if upstreams.is_empty() {
+            vec![self]
+        } else {
+            self.upstream_configs(upstreams)
+        };
+        configs
+            .into_iter()
+            .flat_map(|config| {
+                if config.Top.versions.is_empty() {
+                    return vec![config];
+                }
+                config
+                    .Top
+                    .versions
+                    .iter()
+                    .map(|version| {
+                        let mut version_config = config.clone();
+                        version_config.Top.version = version.clone();
+                        version_config.Top.versions.clear();
+                        version_config
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn upstream_configs(&self, upstreams: Vec<Upstream>) -> Vec<Config> {
+        upstreams
+            .into_iter()
+            .map(|upstream| {
+                let mut config = self.clone();
+                config.Top = Top {
+                    outputDir: self.Top.outputDir.clone(),
+                    mirrorPathTemplate: upstream.Top.mirrorPathTemplate.or_else(|| self.Top.mirrorPathTemplate.clone()),
+                    ..upstream.Top
+                };
+                if let Some(default_spec) = upstream.DefaultPackageSpec {
+                    config.DefaultPackageSpec = default_spec;
+                }
+                config.PackageSpecs.extend(upstream.PackageSpecs);
+                config.compile_templates();
+                config
+            })
+            .collect()
+    }
+
+    // parses the templates of the config once instead of on every render, a
+    // template which does not parse is left to fail when it is rendered
+    fn compile_templates(&mut self) {
+        self.compiled.clear();
+        for (name, template) in validate::templates(self) {
+            // a failed add may leave a broken template behind
+            let mut tera = self.tera.clone();
+            if tera.add_raw_template(&name, &template).is_ok() {
+                self.tera = tera;
+                self.compiled.insert(name, template);
+            }
+        }
+    }
+
+    // returns all packages the resource is copied into together with the
+    // index of the deciding rule, an empty list drops the resource
+    fn classify(&self, resource: &Resource) -> Result<Vec<Placement>, Error> {
+        for (rule_idx, rule) in self.SplitRules.iter().enumerate() {
+            if let Some(package_names) = self.apply_rule(rule_idx, rule, resource)? {
+                return Ok(package_names
+                    .into_iter()
+                    .map(|package_name| Placement {
+                        package_name,
+                        rule_idx: Some(rule_idx),
+                    })
+                    .collect());
+            }
+        }
+
+        let package_name = self.DefaultPackageSpec.defaultName.clone();
+        Ok(vec![Placement {
+            package_name,
+            rule_idx: None,
+        }])
+    }
+
+    // describes for every rule evaluated by classify why it did or did not
+    // decide on the resource
+    fn explain(&self, resource: &Resource) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (rule_idx, rule) in self.SplitRules.iter().enumerate() {
+            if let Some(reason) = rule.matcher.mismatch(resource) {
+                lines.push(format!("SplitRules[{}] does not match: {}", rule_idx, reason));
+                continue;
+            }
+            match self.apply_rule(rule_idx, rule, resource) {
+                Err(err) => {
+                    lines.push(format!("SplitRules[{}] matches, but {}", rule_idx, err));
+                    return lines;
+                }
+                Ok(None) if rule.classify.is_some() => {
+                    lines.push(format!("SplitRules[{}] matches, its classify script returned no package", rule_idx))
+                }
+                Ok(None) => lines.push(format!("SplitRules[{}] matches, but names no package", rule_idx)),
+                Ok(Some(package_names)) if package_names.is_empty() => {
+                    lines.push(format!("SplitRules[{}] matches and drops the resource", rule_idx));
+                    return lines;
+                }
+                Ok(Some(package_names)) => {
+                    lines.push(format!(
+                        "SplitRules[{}] matches and places it in {}",
+                        rule_idx,
+                        package_names.join(", ")
+                    ));
+                    return lines;
+                }
+            }
+        }
+        lines.push(format!(
+            "no rule decided, it goes to the default package {}",
+            self.DefaultPackageSpec.defaultName
+        ));
+        lines
+    }
+
+    // returns None if the rule does not decide on the resource
+    fn apply_rule(&self, rule_idx: usize, rule: &SplitRule, resource: &Resource) -> Result<Option<Vec<String>>, Error> {
+        if !rule.matcher.do_match(resource) {
+            return Ok(None);
+        }
+        if rule.classify.is_some() {
+            return self
+                .scripts
+                .run(rule_idx, &resource.manifest)
+                .map_err(|reason| Error::Script { rule_idx, reason });
+        }
+        if let Some(SplitBy::Namespace) = rule.splitBy {
+            if resource.scope == Scope::Namespaced {
+                let package_name = match (&resource.namespace, &rule.unnamespacedPackageName) {
+                    (Some(namespace), _) => namespace.clone(),
+                    (None, Some(package_name)) => package_name.clone(),
+                    (None, None) => self.DefaultPackageSpec.defaultName.clone(),
+                };
+                return Ok(Some(vec![package_name]));
+            }
+            let package_names = rule.package_names();
+            if package_names.is_empty() {
+                return Ok(None);
+            }
+            return Ok(Some(package_names));
+        }
+        let mut package_names = rule.package_names();
+        if let Some(template) = &rule.packageNameTemplate {
+            let package_name = self.render_package_name(rule_idx, template, resource)?;
+            if !package_name.is_empty() && !package_names.contains(&package_name) {
+                package_names.push(package_name);
+            }
+        }
+        Ok(Some(package_names))
+    }
+
+    // the package specs named by the rules are looked up while rendering and
+    // a package named by several rules must get the same spec from all of them
+    fn check_package_specs(&self) -> Result<(), Error> {
+        match validate::check_rules(self).into_iter().next() {
+            Some(problem) => Err(Error::Config(problem)),
+            None => Ok(()),
+        }
+    }
+
+    // returns the named package spec selected for the package or the default one
+    fn package_spec(&self, package: &Package) -> (String, &PackageSpec) {
+        match &package.spec {
+            Some(spec_name) => {
+                let spec = self
+                    .PackageSpecs
+                    .get(spec_name)
+                    .expect("the package specs of the rules are checked before splitting");
+                (format!("PackageSpecs.{}", spec_name), spec)
+            }
+            None => (
+                "DefaultPackageSpec".to_string(),
+                &self.DefaultPackageSpec.spec,
+            ),
+        }
+    }
+
+    // the resource spec of the deciding rule overrides the one of the package spec
+    fn resource_spec(&self, package: &Package, rule_idx: Option<usize>) -> (String, &ResourceSpec) {
+        if let Some(rule_idx) = rule_idx {
+            if let Some(resource_spec) = &self.SplitRules[rule_idx].resourceSpec {
+                return (format!("SplitRules[{}].resourceSpec", rule_idx), resource_spec);
+            }
+        }
+        let (spec_name, spec) = self.package_spec(package);
+        (format!("{}.resourceSpec", spec_name), &spec.resourceSpec)
+    }
+
+    // returns the index of the first generator rule matching the resource
+    fn generator_rule(&self, resource: &Resource) -> Option<usize> {
+        self.Generators
+            .iter()
+            .position(|rule| rule.matcher.do_match(resource))
+    }
+
+    fn render_generator_dir(&self, rule_idx: usize, package: &Package, resource: &Resource) -> Result<String, Error> {
+        let mut context = self.context(Some(package), package.rule_idx);
+        context.insert("packageName", &package.name);
+        context.insert("resource", &resource);
+
+        let template_name = format!("Generators[{}].dirTemplate", rule_idx);
+        self.render_template(&template_name, &self.Generators[rule_idx].dirTemplate, &context)
+    }
+
+    // base context of all templates, vars of the rule override vars of the
+    // package spec which override the global Variables
+    fn context(&self, package: Option<&Package>, rule_idx: Option<usize>) -> Context {
+        let mut vars = self.Variables.clone();
+        if let Some(package) = package {
+            vars.extend(self.package_spec(package).1.vars.clone());
+        }
+        if let Some(rule_idx) = rule_idx {
+            vars.extend(self.SplitRules[rule_idx].vars.clone());
+        }
+
+        let mut context = Context::new();
+        context.insert("top", &self.Top);
+        context.insert("vars", &vars);
+        context
+    }
+
+    // the yaml of a placed resource and the yaml before the provenance
+    // annotations were stamped
+    fn emit_resource(&self, placed: &PlacedResource, generated_at: &str) -> (String, String) {
+        let source = placed.source.as_deref();
+        let unstamped = emitter::emit(&self.Emitter, &placed.manifest, source);
+        let provenance = match &self.Provenance {
+            Some(provenance) => provenance,
+            None => return (unstamped.clone(), unstamped),
+        };
+        // the digest covers the manifest without the provenance annotations
+        let digest = format!("sha256:{:x}", Sha256::digest(unstamped.as_bytes()));
+        let mut annotations = vec![
+            ("source", self.Top.source.clone().unwrap_or_default()),
+            ("version", self.Top.version.clone()),
+            ("digest", digest),
+        ];
+        if provenance.timestamp {
+            annotations.push(("generated-at", generated_at.to_string()));
+        }
+        let mut manifest = placed.manifest.clone();
+        for (key, value) in annotations {
+            let key = format!("{}{}", provenance.annotationPrefix, key);
+            manifest::set_metadata_entry(&mut manifest, "annotations", &key, &value);
+        }
+        (emitter::emit(&self.Emitter, &manifest, source), unstamped)
+    }
+
+    /// Context of a template outside of a package, `top` and `vars`.
+    pub fn template_context(&self) -> Context {
+        self.context(None, None)
+    }
+
+    /// Renders a template with the environment of the config, which has the
+    /// [`filters`] and the templates of `Templates.dir`.
+    pub fn render_template(&self, template_name: &str, template: &str, context: &Context) -> Result<String, Error> {
+        trace!("render template {}", template_name);
+        let template_error = |source| Error::Template {
+            template: template_name.to_string(),
+            source,
+        };
+        if self.compiled.get(template_name).is_some_and(|compiled| compiled == template) {
+            return self.tera.render(template_name, context).map_err(template_error);
+        }
+        // other templates are added to a copy of the environment
+        let mut tera = self.tera.clone();
+        tera.add_raw_template(template_name, template).map_err(template_error)?;
+        tera.render(template_name, context).map_err(template_error)
+    }
+
+    // renders the path and the files of a package, the patch stubs are
+    // listed by the descriptor
+    fn render_package_files(&self, package: &mut Package) -> Result<PackageFiles, Error> {
+        let pathname = self.render_package_path(package)?;
+        let filename = self.render_package_filename(package)?;
+        package.path = Some(pathname.clone());
+        package.filename = Some(filename.clone());
+        let path = Path::new(&pathname);
+
+        let mut stubs = Vec::new();
+        for (patch_filename, content) in self.render_patch_stubs(package)? {
+            stubs.push((path.join(&patch_filename), content));
+            package.patches.push(patch_filename);
+        }
+        let descriptor = (path.join(filename), self.render_package_descriptor(package)?);
+        let extra = self
+            .render_extra_files(package)?
+            .into_iter()
+            .map(|(filename, content)| (path.join(filename), content))
+            .collect();
+        Ok(PackageFiles { stubs, descriptor, extra })
+    }
+
+    fn render_package_descriptor(&self, package: &Package) -> Result<String, Error> {
+        let (common_labels, common_annotations) = self.render_common_metadata(package)?;
+        let (name_prefix, name_suffix) = self.render_name_affixes(package)?;
+        let mut context = self.context(Some(package), package.rule_idx);
+        context.insert("package", &package);
+        context.insert("namePrefix", name_prefix.as_deref().unwrap_or_default());
+        context.insert("nameSuffix", name_suffix.as_deref().unwrap_or_default());
+        context.insert("commonLabels", &common_labels);
+        context.insert("commonAnnotations", &common_annotations);
+        let image_entries =
+            images::image_entries(&package.images, &self.ImageRewrites, &package.digests);
+        context.insert("images", &image_entries);
+
+        let (spec_name, spec) = self.package_spec(package);
+        let template_name = format!("{}.template", spec_name);
+        let mut package_yaml = self.render_template(&template_name, &spec.template, &context)?;
+
+        if let (true, Some(namespace)) = (spec.autoNamespace, &package.commonNamespace) {
+            append_top_level_field(&mut package_yaml, "namespace", namespace);
+        }
+        if !common_labels.is_empty() {
+            append_top_level_field(&mut package_yaml, "commonLabels", &common_labels);
+        }
+        if !common_annotations.is_empty() {
+            append_top_level_field(&mut package_yaml, "commonAnnotations", &common_annotations);
+        }
+        if let Some(name_prefix) = name_prefix {
+            append_top_level_field(&mut package_yaml, "namePrefix", &name_prefix);
+        }
+        if let Some(name_suffix) = name_suffix {
+            append_top_level_field(&mut package_yaml, "nameSuffix", &name_suffix);
+        }
+        if !image_entries.is_empty() {
+            append_top_level_field(&mut package_yaml, "images", &image_entries);
+        }
+        if !package.configMapGenerator.is_empty() {
+            append_top_level_field(&mut package_yaml, "configMapGenerator", &package.configMapGenerator);
+        }
+        if !package.secretGenerator.is_empty() {
+            append_top_level_field(&mut package_yaml, "secretGenerator", &package.secretGenerator);
+        }
+        if !package.patches.is_empty() {
+            let patches: Vec<BTreeMap<&str, &String>> = package
+                .patches
+                .iter()
+                .map(|path| std::iter::once(("path", path)).collect())
+                .collect();
+            append_top_level_field(&mut package_yaml, "patches", &patches);
+        }
+        let package_replacements =
+            replacements::package_replacements(&self.Replacements, &package.resources);
+        if !package_replacements.is_empty() {
+            append_top_level_field(&mut package_yaml, "replacements", &package_replacements);
+        }
+        Ok(package_yaml)
+    }
+
+    // returns the rendered namePrefix and nameSuffix of the package spec
+    fn render_name_affixes(&self, package: &Package) -> Result<NameAffixes, Error> {
+        let mut context = self.context(Some(package), package.rule_idx);
+        context.insert("packageName", &package.name);
+
+        let (spec_name, spec) = self.package_spec(package);
+        let render = |field: &str, template: &Option<String>| {
+            template
+                .as_ref()
+                .map(|template| self.render_template(&format!("{}.{}", spec_name, field), template, &context))
+                .transpose()
+        };
+        Ok((
+            render("namePrefix", &spec.namePrefix)?,
+            render("nameSuffix", &spec.nameSuffix)?,
+        ))
+    }
+
+    // returns filename and content of a patch stub for every workload of the package
+    fn render_patch_stubs(&self, package: &Package) -> Result<Vec<(String, String)>, Error> {
+        let (spec_name, spec) = self.package_spec(package);
+        let patch_stubs = match &spec.patchStubs {
+            Some(patch_stubs) => patch_stubs,
+            None => return Ok(Vec::new()),
+        };
+
+        let template_name = format!("{}.patchStubs.filenameTemplate", spec_name);
+        let mut stubs = Vec::new();
+        for resource in &package.resources {
+            let is_workload = patch_stubs
+                .kinds
+                .iter()
+                .any(|kind| kind.to_lowercase() == resource.kind.to_lowercase());
+            if !is_workload {
+                continue;
+            }
+            let mut context = self.context(Some(package), package.rule_idx);
+            context.insert("packageName", &package.name);
+            context.insert("resource", &resource);
+            let filename = self.render_template(&template_name, &patch_stubs.filenameTemplate, &context)?;
+
+            let mut content = format!(
+                "# local customizations of {} {}, kept on regeneration\n",
+                resource.kind, resource.name
+            );
+            content.push_str(&format!(
+                "apiVersion: {}\nkind: {}\nmetadata:\n  name: {}\n",
+                resource.manifest["apiVersion"].as_str().unwrap_or_default(),
+                resource.kind,
+                resource.name
+            ));
+            if let Some(namespace) = &resource.namespace {
+                content.push_str(&format!("  namespace: {}\n", namespace));
+            }
+            stubs.push((filename, content));
+        }
+        Ok(stubs)
+    }
+
+    // returns the rendered common labels and annotations of the package spec
+    fn render_common_metadata(
+        &self,
+        package: &Package,
+    ) -> Result<RenderedMetadata, Error> {
+        let mut context = self.context(Some(package), package.rule_idx);
+        context.insert("packageName", &package.name);
+
+        let (spec_name, spec) = self.package_spec(package);
+        let render = |field: &str, values: &BTreeMap<String, String>| {
+            values
+                .iter()
+                .map(|(key, template)| {
+                    let template_name = format!("{}.{}.{}", spec_name, field, key);
+                    Ok((key.clone(), self.render_template(&template_name, template, &context)?))
+                })
+                .collect::<Result<BTreeMap<String, String>, Error>>()
+        };
+        Ok((
+            render("commonLabels", &spec.commonLabels)?,
+            render("commonAnnotations", &spec.commonAnnotations)?,
+        ))
+    }
+
+    // returns the new name of a resource matched by a rule with rename
+    fn render_resource_name(
+        &self,
+        rule_idx: usize,
+        rename: &Rename,
+        package: &Package,
+        resource: &Resource,
+    ) -> Result<String, Error> {
+        let mut context = self.context(Some(package), Some(rule_idx));
+        context.insert("packageName", &package.name);
+        context.insert("resource", resource);
+
+        let template_name = format!("SplitRules[{}].rename.nameTemplate", rule_idx);
+        self.render_template(&template_name, &rename.nameTemplate, &context)
+    }
+
+    // returns the rendered labels and annotations a rule adds to a resource
+    fn render_rule_metadata(
+        &self,
+        rule_idx: usize,
+        package: &Package,
+        resource: &Resource,
+    ) -> Result<RenderedMetadata, Error> {
+        let mut context = self.context(Some(package), Some(rule_idx));
+        context.insert("packageName", &package.name);
+        context.insert("resource", resource);
+
+        let rule = &self.SplitRules[rule_idx];
+        let render = |field: &str, values: &BTreeMap<String, String>| {
+            values
+                .iter()
+                .map(|(key, template)| {
+                    let template_name = format!("SplitRules[{}].{}.{}", rule_idx, field, key);
+                    Ok((key.clone(), self.render_template(&template_name, template, &context)?))
+                })
+                .collect::<Result<BTreeMap<String, String>, Error>>()
+        };
+        Ok((render("labels", &rule.labels)?, render("annotations", &rule.annotations)?))
+    }
+
+    fn render_package_name(&self, rule_idx: usize, template: &str, resource: &Resource) -> Result<String, Error> {
+        let mut context = self.context(None, Some(rule_idx));
+        context.insert("resource", &resource);
+
+        let template_name = format!("SplitRules[{}].packageNameTemplate", rule_idx);
+        Ok(self.render_template(&template_name, template, &context)?.trim().to_string())
+    }
+
+    fn render_changelog_path(&self) -> Result<Option<String>, Error> {
+        let changelog = match &self.Changelog {
+            Some(changelog) => changelog,
+            None => return Ok(None),
+        };
+        let context = self.context(None, None);
+        self.render_template("Changelog.pathTemplate", &changelog.pathTemplate, &context).map(Some)
+    }
+
+    fn render_mirror_path(&self) -> Result<Option<String>, Error> {
+        let template = match &self.Top.mirrorPathTemplate {
+            Some(template) => template,
+            None => return Ok(None),
+        };
+        let context = self.context(None, None);
+        self.render_template("Top.mirrorPathTemplate", template, &context).map(Some)
+    }
+
+    fn render_source(&self) -> Result<String, Error> {
+        let context = self.context(None, None);
+        self.render_template("Top.sourceTemplate", &self.Top.sourceTemplate, &context)
+    }
+
+    fn render_resource_filename(
+        &self,
+        package: &Package,
+        resource: &Resource,
+        rule_idx: Option<usize>,
+    ) -> Result<String, Error> {
+        let (name_prefix, name_suffix) = package.name_affixes.clone().unwrap_or_default();
+        let mut context = self.context(Some(package), rule_idx);
+        context.insert("packageName", &package.name);
+        context.insert("namePrefix", &name_prefix.unwrap_or_default());
+        context.insert("nameSuffix", &name_suffix.unwrap_or_default());
+        context.insert("resource", &resource);
+
+        let (spec_name, resource_spec) = self.resource_spec(package, rule_idx);
+        let (template_name, template) = match resource_spec.group_filename_template() {
+            None => ("filenameTemplate", resource_spec.filenameTemplate.as_str()),
+            Some(template) => ("groupFilenameTemplate", template),
+        };
+        let template_name = format!("{}.{}", spec_name, template_name);
+        self.render_template(&template_name, template, &context)
+    }
+
+    fn render_resource_path(
+        &self,
+        package: &Package,
+        resource: &Resource,
+        rule_idx: Option<usize>,
+    ) -> Result<String, Error> {
+        let (name_prefix, name_suffix) = package.name_affixes.clone().unwrap_or_default();
+        let mut context = self.context(Some(package), rule_idx);
+        context.insert("packageName", &package.name);
+        context.insert("namePrefix", &name_prefix.unwrap_or_default());
+        context.insert("nameSuffix", &name_suffix.unwrap_or_default());
+        context.insert("resource", &resource);
+
+        let (spec_name, resource_spec) = self.resource_spec(package, rule_idx);
+        let template_name = format!("{}.pathTemplate", spec_name);
+        self.render_template(&template_name, &resource_spec.pathTemplate, &context)
+    }
+    fn render_package_filename(&self, package: &Package) -> Result<String, Error> {
+        let mut context = self.context(Some(package), package.rule_idx);
+        context.insert("packageName", &package.name);
+
+        let (spec_name, spec) = self.package_spec(package);
+        let template_name = format!("{}.filenameTemplate", spec_name);
+        self.render_template(&template_name, &spec.filenameTemplate, &context)
+    }
+
+    fn render_package_path(&self, package: &Package) -> Result<String, Error> {
+        let mut context = self.context(Some(package), package.rule_idx);
+        context.insert("packageName", &package.name);
+
+        let (spec_name, spec) = self.package_spec(package);
+        let template_name = format!("{}.pathTemplate", spec_name);
+        self.render_template(&template_name, &spec.pathTemplate, &context)
+    }
+
+    // returns filename and content of all extra files of the package
+    fn render_extra_files(&self, package: &Package) -> Result<Vec<(String, String)>, Error> {
+        let mut context = self.context(Some(package), package.rule_idx);
+        context.insert("packageName", &package.name);
+        context.insert("package", &package);
+
+        let (spec_name, spec) = self.package_spec(package);
+        let mut files = Vec::new();
+        for (idx, extra_file) in spec.extraFiles.iter().enumerate() {
+            let template_name = format!("{}.extraFiles[{}].filenameTemplate", spec_name, idx);
+            let filename = self.render_template(&template_name, &extra_file.filenameTemplate, &context)?;
+
+            let template_name = format!("{}.extraFiles[{}].template", spec_name, idx);
+            let content = self.render_template(&template_name, &extra_file.template, &context)?;
+            files.push((filename, content));
+        }
+        Ok(files)
+    }
+
+    // returns path and content of the overlay descriptor and its patch stubs
+    fn render_overlay(&self, overlay_name: &str, packages: &[Package]) -> Result<Vec<(PathBuf, String)>, Error> {
+        let overlay = &self.Overlays[overlay_name];
+        let spec_name = format!("Overlays.{}", overlay_name);
+        let mut context = self.context(None, None);
+        let mut vars = self.Variables.clone();
+        vars.extend(overlay.vars.clone());
+        context.insert("vars", &vars);
+        let mut overlay_context = HashMap::new();
+        overlay_context.insert("name", overlay_name);
+        context.insert("overlay", &overlay_context);
+
+        let render = |field: &str, template: &str, context: &Context| {
+            let template_name = format!("{}.{}", spec_name, field);
+            self.render_template(&template_name, template, context)
+        };
+
+        let pathname = render("pathTemplate", &overlay.pathTemplate, &context)?;
+        let path = Path::new(&pathname);
+        let packages: Vec<Package> = packages
+            .iter()
+            .filter(|package| match &overlay.packages {
+                Some(names) => names.contains(&package.name),
+                None => true,
+            })
+            .map(|package| {
+                let mut package = package.clone();
+                let package_path = Path::new(package.path.as_deref().unwrap_or_default());
+                package.relativePath = Some(relative_path(path, package_path));
+                package
+            })
+            .collect();
+        insert_packages(&mut context, &packages);
+
+        let mut files = Vec::new();
+        let mut patch_filenames = Vec::new();
+        for (idx, patch) in overlay.patches.iter().enumerate() {
+            let filename = render(
+                &format!("patches[{}].filenameTemplate", idx),
+                &patch.filenameTemplate,
+                &context,
+            )?;
+            let content = render(&format!("patches[{}].template", idx), &patch.template, &context)?;
+            files.push((path.join(&filename), content));
+            patch_filenames.push(filename);
+        }
+        context.insert("patches", &patch_filenames);
+
+        let filename = render("filenameTemplate", &overlay.filenameTemplate, &context)?;
+        let content = render("template", &overlay.template, &context)?;
+        files.insert(0, (path.join(filename), content));
+        Ok(files)
+    }
+
+    fn render_root_descriptor(&self, root_spec: &RootSpec, packages: &[Package]) -> Result<String, Error> {
+        let mut context = self.context(None, None);
+        insert_packages(&mut context, packages);
+
+        self.render_template("RootSpec.template", &root_spec.template, &context)
+    }
+
+    fn render_root_filename(&self, root_spec: &RootSpec) -> Result<String, Error> {
+        let context = self.context(None, None);
+        self.render_template("RootSpec.filenameTemplate", &root_spec.filenameTemplate, &context)
+    }
+
+    fn render_root_path(&self, root_spec: &RootSpec) -> Result<String, Error> {
+        let context = self.context(None, None);
+        self.render_template("RootSpec.pathTemplate", &root_spec.pathTemplate, &context)
+    }
+}
+
+impl Package {
+    fn sort_resources(&mut self, sort_by: SortBy, crds_first: bool) {
+        match sort_by {
+            SortBy::None => {}
+            SortBy::Index => self.resources.sort_by_key(|r| r.index),
+            SortBy::Kind => self
+                .resources
+                .sort_by(|a, b| (&a.kind, &a.namespace, &a.name).cmp(&(&b.kind, &b.namespace, &b.name))),
+            SortBy::Name => self
+                .resources
+                .sort_by(|a, b| (&a.name, &a.kind, &a.namespace).cmp(&(&b.name, &b.kind, &b.namespace))),
+        }
+        if crds_first {
+            self.resources.sort_by_key(|r| apply_order(&r.kind));
+        }
+    }
+
+    fn set_component(&mut self, component: bool) {
+        self.component = component;
+        if component {
+            self.apiVersion = "kustomize.config.k8s.io/v1alpha1".to_string();
+            self.kind = "Component".to_string();
+        } else {
+            self.apiVersion = "kustomize.config.k8s.io/v1beta1".to_string();
+            self.kind = "Kustomization".to_string();
+        }
+    }
+
+    fn aggregate(&mut self) {
+        self.kinds.clear();
+        self.namespaces.clear();
+        self.byKind.clear();
+        self.images.clear();
+        self.files.clear();
+        for resource in &self.resources {
+            if let Some(filename) = &resource.filename {
+                if !self.files.contains(filename) {
+                    self.files.push(filename.clone());
+                }
+            }
+            for image in &resource.images {
+                if !self.images.contains(image) {
+                    self.images.push(image.clone());
+                }
+            }
+            if !self.kinds.contains(&resource.kind) {
+                self.kinds.push(resource.kind.clone());
+            }
+            if let Some(namespace) = &resource.namespace {
+                if !self.namespaces.contains(namespace) {
+                    self.namespaces.push(namespace.clone());
+                }
+            }
+            self.byKind
+                .entry(resource.kind.clone())
+                .or_default()
+                .push(resource.clone());
+        }
+        self.resourceCount = self.resources.len();
+
+        let mut namespaced = self
+            .resources
+            .iter()
+            .filter(|resource| resource.scope == Scope::Namespaced)
+            .map(|resource| &resource.namespace);
+        self.commonNamespace = match namespaced.next() {
+            Some(Some(namespace)) if namespaced.all(|other| other.as_ref() == Some(namespace)) => {
+                Some(namespace.clone())
+            }
+            _ => None,
+        };
+    }
+}
+
+impl RootSpec {
+    fn load_template_files(&mut self, base_dir: &Path) -> Result<(), String> {
+        load_template_file(base_dir, &self.templateFile, &mut self.template)?;
+        load_template_file(base_dir, &self.filenameTemplateFile, &mut self.filenameTemplate)?;
+        load_template_file(base_dir, &self.pathTemplateFile, &mut self.pathTemplate)?;
+        Ok(())
+    }
+}
+
+impl PackageSpec {
+    fn load_template_files(&mut self, base_dir: &Path) -> Result<(), String> {
+        load_template_file(base_dir, &self.templateFile, &mut self.template)?;
+        load_template_file(base_dir, &self.filenameTemplateFile, &mut self.filenameTemplate)?;
+        load_template_file(base_dir, &self.pathTemplateFile, &mut self.pathTemplate)?;
+        self.resourceSpec.load_template_files(base_dir)?;
+        for extra_file in self.extraFiles.iter_mut() {
+            load_template_file(base_dir, &extra_file.templateFile, &mut extra_file.template)?;
+        }
+        Ok(())
+    }
+}
+
+impl ResourceSpec {
+    fn load_template_files(&mut self, base_dir: &Path) -> Result<(), String> {
+        load_template_file(base_dir, &self.pathTemplateFile, &mut self.pathTemplate)?;
+        load_template_file(base_dir, &self.filenameTemplateFile, &mut self.filenameTemplate)?;
+        Ok(())
+    }
+
+    // the template naming the file of a group, None if resources are not grouped
+    fn group_filename_template(&self) -> Option<&str> {
+        match (self.groupBy, &self.groupFilenameTemplate) {
+            (GroupBy::None, _) => None,
+            (_, Some(template)) => Some(template),
+            (GroupBy::Package, None) => Some("resources.yaml"),
+            (GroupBy::Kind, None) => Some("{{resource.kind | kebab}}.yaml"),
+        }
+    }
+}
+
+// paths rendered from templates are relative to the output directory and
+// must stay inside it
+fn leaves_output_dir(path: &Path) -> bool {
+    path.components()
+        .any(|c| matches!(c, std::path::Component::ParentDir | std::path::Component::RootDir | std::path::Component::Prefix(_)))
+}
+
+// checks every component of a path against the NTFS naming rules
+fn check_portable(path: &Path) -> Result<(), String> {
+    const RESERVED: &[&str] = &["con", "prn", "aux", "nul"];
+    for component in path.components() {
+        let name = match component {
+            std::path::Component::Normal(name) => name.to_string_lossy(),
+            _ => continue,
+        };
+        if let Some(c) = name.chars().find(|c| "<>:\"|?*\\".contains(*c) || c.is_control()) {
+            return Err(format!("{} contains {:?}", name, c));
+        }
+        if name.ends_with('.') || name.ends_with(' ') {
+            return Err(format!("{} ends with a dot or space", name));
+        }
+        let stem = name.split('.').next().unwrap_or_default().to_lowercase();
+        let is_device = |prefix: &str| {
+            stem.len() == 4 && stem.starts_with(prefix) && stem[3..].chars().all(|c| c.is_ascii_digit())
+        };
+        if RESERVED.contains(&stem.as_str()) || is_device("com") || is_device("lpt") {
+            return Err(format!("{} is a reserved name", name));
+        }
+    }
+    Ok(())
+}
+
+// upgrades a config file in place to the current apiVersion
+fn migrate(config_path: &Path) -> Result<(), Error> {
+    if let Some("toml") | Some("json") = config_path.extension().and_then(|ext| ext.to_str()) {
+        return Err(Error::Usage(format!(
+            "{}: only yaml configs can be migrated, set apiVersion to {} by hand",
+            config_path.display(),
+            migrate::API_VERSION
+        )));
+    }
+    let config = fs::read_to_string(config_path).map_err(Error::file("read", config_path))?;
+    let (migrated, applied) =
+        migrate::migrate(&config).map_err(|err| Error::Config(format!("{}: {}", config_path.display(), err)))?;
+    if applied.is_empty() {
+        println!("{} is up to date", config_path.display());
+        return Ok(());
+    }
+    fs::write(config_path, migrated).map_err(Error::file("write", config_path))?;
+    info!("migrate file: {} ({})", config_path.display(), applied.join(", "));
+    Ok(())
+}
+
+// sets Top.version in the config file to the requested version and prints
+// the change, e.g. for a commit message
+fn bump(options: &Options) -> Result<(), Error> {
+    let config_path = Path::new(&options.config_path);
+    let config = Config::load(config_path, options.profile.as_deref(), &options.sets)?;
+    let version = match &options.to {
+        Some(version) => version.clone(),
+        None => bump::latest_release(&config.Top.sourceTemplate, &config.Top.version).map_err(|reason| Error::Fetch {
+            url: config.Top.sourceTemplate.clone(),
+            reason,
+        })?,
+    };
+    let text = fs::read_to_string(config_path).map_err(Error::file("read", config_path))?;
+    let extension = config_path.extension().and_then(|ext| ext.to_str());
+    let (bumped, old) = bump::set_version(&text, extension, &version)
+        .map_err(|err| Error::Config(format!("{}: {}", config_path.display(), err)))?;
+    if old == version {
+        println!("{} is already at {}", config.Top.name, version);
+        return Ok(());
+    }
+    fs::write(config_path, bumped).map_err(Error::file("write", config_path))?;
+    println!("{} {} → {}", config.Top.name, old, version);
+    Ok(())
+}
+
+// classifies the upstream manifests of two versions without writing
+// anything and prints the resources which differ per package
+fn diff_versions(options: &Options) -> Result<(), Error> {
+    let config = Config::load(Path::new(&options.config_path), options.profile.as_deref(), &options.sets)?;
+    let mut configs = config.upstreams();
+    if configs.len() != 1 {
+        return Err(Error::Usage(
+            "diff-versions compares a single upstream, the config lists several upstreams or versions".to_string(),
+        ));
+    }
+    let config = configs.remove(0);
+    let from = options.from.clone().unwrap_or_else(|| config.Top.version.clone());
+    let mut version_configs: Vec<Config> = vec![from, options.to.clone().unwrap_or_default()]
+        .into_iter()
+        .map(|version| {
+            let mut version_config = config.clone();
+            version_config.Top.version = version;
+            version_config
+        })
+        .collect();
+    let mut output = output::Output::new(None, output::Mode::Inspect, false, false);
+    let mut reports = Vec::new();
+    for run in generate(&mut version_configs, options, &mut output) {
+        if let Some(run) = run? {
+            reports.push(run.report);
+        }
+    }
+    print!("{}", report::compare(&reports[0], &reports[1]));
+    Ok(())
+}
+
+// shows the package of every upstream resource, lets the user move
+// resources or kinds to other packages and adds the matching split rules to
+// the config
+fn review(options: &Options) -> Result<(), Error> {
+    let config_path = Path::new(&options.config_path);
+    if let Some("toml") | Some("json") = config_path.extension().and_then(|ext| ext.to_str()) {
+        return Err(Error::Usage(format!("{}: only yaml configs can be reviewed", config_path.display())));
+    }
+    let configs = Config::load(config_path, options.profile.as_deref(), &options.sets)?.upstreams();
+    let mut resources = Vec::new();
+    for config in &configs {
+        let manifests = fetch_manifests(&config.render_source()?)?;
+        for (idx, manifest) in manifests.iter().enumerate() {
+            if let Ok(Some(resource)) = Resource::from_manifest(manifest, idx as u32) {
+                resources.push(resource);
+            }
+        }
+    }
+    // the split rules are shared by all upstreams
+    let stdin = std::io::stdin();
+    let assignments = review::review(&configs[0], &resources, &mut stdin.lock(), &mut std::io::stdout())
+        .map_err(Error::file("read", "stdin"))?;
+    match assignments {
+        None => println!("{} left unchanged", config_path.display()),
+        Some(assignments) if assignments.is_empty() => println!("no split rules to add"),
+        Some(assignments) => {
+            let text = fs::read_to_string(config_path).map_err(Error::file("read", config_path))?;
+            let text = review::insert_rules(&text, &assignments)
+                .map_err(|err| Error::Config(format!("{}: {}", config_path.display(), err)))?;
+            fs::write(config_path, text).map_err(Error::file("write", config_path))?;
+            println!("add {} split rules to {}", assignments.len(), config_path.display());
+        }
+    }
+    Ok(())
+}
+
+// the documents at url which parse, with lists expanded into their items
+fn fetch_manifests(url: &str) -> Result<Vec<Yaml>, Error> {
+    let (body, charset) = fetch::fetch(url)?;
+    let mut manifests = Vec::new();
+    for document in manifest::read_manifests(BufReader::new(body), charset.as_deref()) {
+        if let Ok(document) = document.map_err(Error::file("read", url))? {
+            manifests.extend(manifest::expand_list(document).into_iter().map(|document| document.yaml));
+        }
+    }
+    Ok(manifests)
+}
+
+// writes a starter config for the manifests at url
+fn init(url: &str, config_path: &Path, force: bool) -> Result<(), Error> {
+    if config_path.exists() && !force {
+        return Err(Error::Refused(vec![config_path.to_path_buf()]));
+    }
+    let manifests = fetch_manifests(url)?;
+    fs::write(config_path, init::starter_config(url, &manifests)).map_err(Error::file("write", config_path))?;
+    info!("create file: {}", config_path.display());
+    Ok(())
+}
+
+// table of the packages and their resource counts followed by the totals
+fn summary(packages: &[Package], skipped: usize, dropped: usize, warnings: usize) -> String {
+    let width = packages
+        .iter()
+        .map(|package| package.name.len())
+        .chain(std::iter::once("package".len()))
+        .max()
+        .unwrap();
+    let mut summary = format!("\n{:width$}  resources  generators\n", "package", width = width);
+    let mut resources = 0;
+    for package in packages {
+        let generators = package.configMapGenerator.len() + package.secretGenerator.len();
+        summary.push_str(&format!(
+            "{:width$}  {:>9}  {:>10}\n",
+            package.name,
+            package.resources.len(),
+            generators,
+            width = width
+        ));
+        resources += package.resources.len() + generators;
+    }
+    summary.push_str(&format!(
+        "\n{} resources in {} packages, {} skipped documents, {} dropped resources, {} warnings\n",
+        resources,
+        packages.len(),
+        skipped,
+        dropped,
+        warnings
+    ));
+    summary
+}
+
+// inserts -<n> before the extension, e.g. "service.yaml" -> "service-1.yaml"
+fn dedup_filename(filename: &str, n: usize) -> String {
+    let path = Path::new(filename);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => {
+            let name = format!("{}-{}.{}", stem.to_string_lossy(), n, extension.to_string_lossy());
+            path.with_file_name(name).to_string_lossy().to_string()
+        }
+        _ => format!("{}-{}", filename, n),
+    }
+}
+
+// namespaces and CRDs have to exist before the resources using them
+fn apply_order(kind: &str) -> u8 {
+    match kind {
+        "Namespace" => 0,
+        "CustomResourceDefinition" => 1,
+        _ => 2,
+    }
+}
+
+// appends a top level field to a rendered descriptor unless the template
+// already sets it, keeping the formatting of the rendered template
+fn append_top_level_field<T: Serialize>(yaml: &mut String, key: &str, value: &T) {
+    let prefix = format!("{}:", key);
+    if yaml.lines().any(|line| line.starts_with(&prefix)) {
+        return;
+    }
+    let mut field = BTreeMap::new();
+    field.insert(key, value);
+    let field_yaml = serde_yaml::to_string(&field).unwrap();
+    if !yaml.is_empty() && !yaml.ends_with('\n') {
+        yaml.push('\n');
+    }
+    yaml.push_str(field_yaml.trim_start_matches("---\n"));
+    if !yaml.ends_with('\n') {
+        yaml.push('\n');
+    }
+}
+
+// inserts all packages and additionally the packages split into the
+// resources and components of a referencing kustomization
+fn insert_packages(context: &mut Context, packages: &[Package]) {
+    let (components, resources): (Vec<&Package>, Vec<&Package>) =
+        packages.iter().partition(|package| package.component);
+    context.insert("packages", &packages);
+    context.insert("resources", &resources);
+    context.insert("components", &components);
+}
+
+// returns the path of `to` relative to the directory `from` using `/` as separator
+fn relative_path(from: &Path, to: &Path) -> String {
+    let from: Vec<_> = from.components().filter(|c| c.as_os_str() != ".").collect();
+    let to: Vec<_> = to.components().filter(|c| c.as_os_str() != ".").collect();
+    let common = from.iter().zip(to.iter()).take_while(|(a, b)| a == b).count();
+
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    for component in &to[common..] {
+        parts.push(component.as_os_str().to_string_lossy().to_string());
+    }
+    if parts.is_empty() {
+        return ".".to_string();
+    }
+    parts.join("/")
+}
+
+// replaces the inline template with the content of the template file if given
+fn load_template_file(base_dir: &Path, template_file: &Option<String>, template: &mut String) -> Result<(), String> {
+    if let Some(template_file) = template_file {
+        let template_path = base_dir.join(template_file);
+        *template = fs::read_to_string(&template_path)
+            .map_err(|err| format!("unable to read template file {}: {}", template_path.display(), err))?;
+    }
+    Ok(())
+}
+
+impl SplitRule {
+    fn package_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.packageName.iter().cloned().collect();
+        for name in self.packageNames.iter().flatten() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
+    }
+}
+
+impl Matcher {
+    fn do_match(&self, resource: &Resource) -> bool {
+        self.mismatch(resource).is_none()
+    }
+
+    // the first criterion the resource does not meet, None if it matches
+    fn mismatch(&self, resource: &Resource) -> Option<String> {
+        if let Some(kind) = &self.kind {
+            if kind.to_lowercase() != resource.kind.to_lowercase() {
+                return Some(format!("kind is {}, not {}", resource.kind, kind));
+            }
+        }
+        if let Some(name) = &self.name {
+            if name.to_lowercase() != resource.name.to_lowercase() {
+                return Some(format!("name is {}, not {}", resource.name, name));
+            }
+        }
+        if let Some(namespace) = &self.namespace {
+            if Some(namespace.to_lowercase()) != resource.namespace.as_ref().map(|s| s.to_lowercase()) {
+                let actual = resource.namespace.as_deref().unwrap_or("unset");
+                return Some(format!("namespace is {}, not {}", actual, namespace));
+            }
+        }
+        if let Some(scope) = self.scope {
+            if scope != resource.scope {
+                return Some(format!("scope is {:?}, not {:?}", resource.scope, scope));
+            }
+        }
+        None
+    }
+}
+
+impl Resource {
+    // None for documents without a kind, e.g. empty ones, an error for
+    // resources which can not be written under a name
+    fn from_manifest(manifest: &Yaml, idx: u32) -> Result<Option<Resource>, String> {
+        let kind = if let Some(kind) = manifest["kind"].as_str() {
+            kind
+        } else {
+            return Ok(None);
+        };
+        let name = match manifest["metadata"]["name"].as_str() {
+            Some(name) => name,
+            None => {
+                let reason = match manifest["metadata"]["generateName"].as_str() {
+                    Some(generate_name) => format!("generateName {} instead of a name", generate_name),
+                    None => "no metadata.name".to_string(),
+                };
+                return Err(format!("document {}: {} has {}", idx, kind, reason));
+            }
+        };
+        let namespace = manifest["metadata"]["namespace"]
+            .as_str()
+            .map(|s| s.to_string());
+
+        let identity = format!("{}/{}/{}", kind, namespace.clone().unwrap_or_default(), name);
+        let resource = Resource {
+            index: idx,
+            id: filters::slugify(&identity),
+            idHash: format!("{:x}", Sha256::digest(identity.as_bytes()))[..12].to_string(),
+            name: name.to_string(),
+            kind: kind.to_string(),
+            scope: Scope::of(kind, &namespace),
+            namespace,
+            filename: None,
+            path: None,
+            images: images::find_images(manifest),
+            manifest: yaml_to_value(manifest),
+        };
+        Ok(Some(resource))
+    }
+}
+
+// bounds of a classify script, a runaway loop fails the resource instead
+// of hanging the run
+const SCRIPT_MAX_OPERATIONS: u64 = 1_000_000;
+const SCRIPT_MAX_EXPR_DEPTH: usize = 64;
+const SCRIPT_MAX_FUNCTION_EXPR_DEPTH: usize = 32;
+
+// the classify scripts of the split rules compiled once by rule index and
+// the engine evaluating them
+#[derive(Debug)]
+struct ClassifyScripts {
+    engine: rhai::Engine,
+    asts: HashMap<usize, rhai::AST>,
+}
+
+impl Default for ClassifyScripts {
+    fn default() -> Self {
+        let mut engine = rhai::Engine::new();
+        engine.set_max_operations(SCRIPT_MAX_OPERATIONS);
+        engine.set_max_expr_depths(SCRIPT_MAX_EXPR_DEPTH, SCRIPT_MAX_FUNCTION_EXPR_DEPTH);
+        ClassifyScripts {
+            engine,
+            asts: HashMap::new(),
+        }
+    }
+}
+
+impl ClassifyScripts {
+    fn compile(rules: &[SplitRule]) -> Result<ClassifyScripts, String> {
+        let mut scripts = ClassifyScripts::default();
+        for (rule_idx, rule) in rules.iter().enumerate() {
+            if let Some(script) = &rule.classify {
+                let ast = scripts
+                    .engine
+                    .compile(script)
+                    .map_err(|err| format!("SplitRules[{}].classify: {}", rule_idx, err))?;
+                scripts.asts.insert(rule_idx, ast);
+            }
+        }
+        Ok(scripts)
+    }
+
+    // evaluates the classify script of a rule with the manifest bound to
+    // `manifest`, the script returns a package name, a list of package names,
+    // "skip" to drop the resource or nothing to let the next rule decide
+    fn run(&self, rule_idx: usize, manifest: &tera::Value) -> Result<Option<Vec<String>>, String> {
+        let ast = self
+            .asts
+            .get(&rule_idx)
+            .ok_or_else(|| "the classify script was not compiled".to_string())?;
+        let mut scope = rhai::Scope::new();
+        let manifest = rhai::serde::to_dynamic(manifest).map_err(|err| err.to_string())?;
+        scope.push_dynamic("manifest", manifest);
+        let result = self
+            .engine
+            .eval_ast_with_scope::<rhai::Dynamic>(&mut scope, ast)
+            .map_err(|err| err.to_string())?;
+
+        if result.is_unit() {
+            return Ok(None);
+        }
+        if result.is_string() {
+            let package_name = result.to_string();
+            if package_name == "skip" {
+                return Ok(Some(Vec::new()));
+            }
+            return Ok(Some(vec![package_name]));
+        }
+        if result.is_array() {
+            return result
+                .into_array()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|name| match name.is_string() {
+                    true => Ok(name.to_string()),
+                    false => Err(format!("returned a list holding {} {}, expected package names", name.type_name(), name)),
+                })
+                .collect::<Result<Vec<String>, String>>()
+                .map(Some);
+        }
+        Err(format!(
+            "returned {} {}, expected a package name, a list of package names or nothing",
+            result.type_name(),
+            result
+        ))
+    }
+}
+
+// converts a yaml document into the json value model used by tera and rhai
+fn yaml_to_value(yaml: &Yaml) -> tera::Value {
+    match yaml {
+        Yaml::Real(real) => real
+            .parse::<f64>()
+            .ok()
+            .and_then(tera::Number::from_f64)
+            .map(tera::Value::Number)
+            .unwrap_or_else(|| tera::Value::String(real.clone())),
+        Yaml::Integer(int) => tera::Value::from(*int),
+        Yaml::String(string) => tera::Value::String(string.clone()),
+        Yaml::Boolean(boolean) => tera::Value::Bool(*boolean),
+        Yaml::Array(array) => tera::Value::Array(array.iter().map(yaml_to_value).collect()),
+        Yaml::Hash(hash) => {
+            let mut map = tera::Map::new();
+            for (key, value) in hash {
+                let key = match key {
+                    Yaml::String(key) | Yaml::Real(key) => key.clone(),
+                    Yaml::Integer(key) => key.to_string(),
+                    Yaml::Boolean(key) => key.to_string(),
+                    _ => continue,
+                };
+                map.insert(key, yaml_to_value(value));
+            }
+            tera::Value::Object(map)
+        }
+        Yaml::Alias(_) | Yaml::Null | Yaml::BadValue => tera::Value::Null,
+    }
+}
+
+impl Scope {
+    // well-known kinds are looked up in the built-in tables, any other kind
+    // is namespaced if and only if the manifest carries a namespace
+    fn of(kind: &str, namespace: &Option<String>) -> Scope {
+        let kind = kind.to_lowercase();
+        if CLUSTER_SCOPED_KINDS.contains(&kind.as_str()) {
+            Scope::Cluster
+        } else if namespace.is_some() || NAMESPACED_KINDS.contains(&kind.as_str()) {
+            Scope::Namespaced
+        } else {
+            Scope::Cluster
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_portable_rejects_reserved_names() {
+        for path in ["out/CON", "out/aux.yaml", "out/Nul.tar.gz", "out/com1.yaml", "out/LPT9", "prn/service.yaml"] {
+            assert!(check_portable(Path::new(path)).is_err(), "{}", path);
+        }
+        for path in ["out/console.yaml", "out/auxiliary.yaml", "out/com10.yaml", "out/lpt.yaml"] {
+            assert_eq!(check_portable(Path::new(path)), Ok(()), "{}", path);
+        }
+    }
+
+    #[test]
+    fn check_portable_rejects_ntfs_invalid_characters() {
+        for c in "<>:\"|?*".chars() {
+            let path = format!("out/config{}map.yaml", c);
+            assert!(check_portable(Path::new(&path)).is_err(), "{}", path);
+        }
+        assert!(check_portable(Path::new("out/config\tmap.yaml")).is_err());
+        assert_eq!(check_portable(Path::new("out/config-map_1.yaml")), Ok(()));
+    }
+
+    #[test]
+    fn check_portable_rejects_trailing_dot_or_space() {
+        for path in ["out/service.", "out/service ", "out./service.yaml", "out /service.yaml"] {
+            assert!(check_portable(Path::new(path)).is_err(), "{:?}", path);
+        }
+        // current and parent directories are no names
+        assert_eq!(check_portable(Path::new("../vendor/./service.yaml")), Ok(()));
+    }
+
+    #[test]
+    fn check_portable_joins_components() {
+        let path = PathBuf::from("out").join("crds").join("service.yaml");
+        assert_eq!(check_portable(&path), Ok(()));
+        // a backslash rendered into a file name is a separator on Windows only
+        #[cfg(not(windows))]
+        assert!(check_portable(&PathBuf::from("out").join("crds\\service.yaml")).is_err());
+    }
+
+    #[test]
+    fn split_manifests_prepares_deserialized_configs() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+Top:
+  name: test
+  version: 1.0.0
+  sourceTemplate: https://example.com/{{top.version}}.yaml
+DefaultPackageSpec:
+  template: "resources: []"
+  pathTemplate: "{{packageName}}"
+  filenameTemplate: kustomization.yaml
+  defaultName: main
+  resourceSpec:
+    pathTemplate: "{{packageName}}"
+    filenameTemplate: "{{resource.kind | kebab}}.yaml"
+SplitRules: []
+"#,
+        )
+        .unwrap();
+        let manifests = "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: example\n";
+        let result = split_manifests(manifests.as_bytes(), &config).unwrap();
+        let paths: Vec<&PathBuf> = result.files.iter().map(|(path, _)| path).collect();
+        assert!(paths.contains(&&PathBuf::from("main/config-map.yaml")), "{:?}", paths);
+    }
+
+    fn classify(script: &str) -> Result<Option<Vec<String>>, String> {
+        let rule: SplitRule = serde_yaml::from_str(&format!("classify: {:?}", script)).unwrap();
+        let manifest = serde_json::json!({"kind": "ConfigMap", "metadata": {"name": "example"}});
+        ClassifyScripts::compile(&[rule])?.run(0, &manifest)
+    }
+
+    #[test]
+    fn classify_scripts_name_packages() {
+        assert_eq!(classify("manifest.metadata.name"), Ok(Some(vec!["example".to_string()])));
+        assert_eq!(classify(r#"["config", manifest.kind]"#), Ok(Some(vec!["config".to_string(), "ConfigMap".to_string()])));
+        assert_eq!(classify(r#""skip""#), Ok(Some(Vec::new())));
+        assert_eq!(classify("if manifest.kind == \"Secret\" { \"secrets\" }"), Ok(None));
+        assert_eq!(classify("return;"), Ok(None));
+    }
+
+    #[test]
+    fn classify_scripts_reject_other_results() {
+        for script in ["42", "true", "#{ name: \"config\" }", r#"["config", 1]"#] {
+            assert!(classify(script).is_err(), "{}", script);
+        }
+        assert!(classify("let x = ;").unwrap_err().starts_with("SplitRules[0].classify"));
+        // a runaway loop fails instead of hanging the run
+        assert!(classify("loop { }").is_err());
+    }
+
+    #[test]
+    fn split_manifests_rejects_paths_leaving_the_output_dir() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+Top:
+  name: test
+  version: 1.0.0
+  sourceTemplate: https://example.com/{{top.version}}.yaml
+DefaultPackageSpec:
+  template: "resources: []"
+  pathTemplate: "{{packageName}}"
+  filenameTemplate: kustomization.yaml
+  defaultName: main
+  resourceSpec:
+    pathTemplate: "{{packageName}}/{{resource.namespace}}"
+    filenameTemplate: "{{resource.name}}.yaml"
+SplitRules: []
+"#,
+        )
+        .unwrap();
+        for namespace in ["..", "../.."] {
+            let manifests = format!("apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: example\n  namespace: {}\n", namespace);
+            match split_manifests(manifests.as_bytes(), &config) {
+                Err(Error::Layout(_)) => {}
+                other => panic!("{}: expected a layout error, got {:?}", namespace, other.map(|result| result.files)),
+            }
+        }
+        assert!(leaves_output_dir(Path::new("/vendor/main")));
+        assert!(!leaves_output_dir(Path::new("vendor/./main/example.yaml")));
+    }
+}
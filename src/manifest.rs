@@ -0,0 +1,315 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use tracing::warn;
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlLoader};
+
+// a parsed document and, for yaml input, the text it was parsed from
+pub struct Document {
+    pub yaml: Yaml,
+    pub source: Option<String>,
+}
+
+// reads the documents of a downloaded source one at a time, lines end with
+// LF, the byte order mark is dropped, latin-1 is used if declared by the
+// charset or from the first line which is not valid UTF-8 on, a json object
+// or array is read as a whole, the iteration ends with the first error of
+// the reader
+pub fn read_manifests<R: BufRead>(reader: R, charset: Option<&str>) -> Manifests<R> {
+    Manifests {
+        reader,
+        latin1: matches!(charset, Some("iso-8859-1") | Some("latin1") | Some("latin-1")),
+        line: 0,
+        lines: VecDeque::new(),
+        pending: VecDeque::new(),
+        done: false,
+    }
+}
+
+pub struct Manifests<R> {
+    reader: R,
+    latin1: bool,
+    // lines read so far and the decoded lines not yet read
+    line: usize,
+    lines: VecDeque<String>,
+    // parsed documents not yet returned, one text may hold several
+    pending: VecDeque<Result<Document, String>>,
+    done: bool,
+}
+
+impl<R: BufRead> Iterator for Manifests<R> {
+    type Item = io::Result<Result<Document, String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && !self.done {
+            if let Err(err) = self.read_document() {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+impl<R: BufRead> Manifests<R> {
+    // parses the lines up to the next document marker
+    fn read_document(&mut self) -> io::Result<()> {
+        let first_line = self.line + 1;
+        let mut text = String::new();
+        while let Some(line) = self.next_line()? {
+            if is_marker(&line) {
+                self.pending.extend(parse_document(first_line, &text));
+                return Ok(());
+            }
+            let json = first_line == 1 && text.trim().is_empty() && line.trim_start().starts_with(['{', '[']);
+            text.push_str(&line);
+            text.push('\n');
+            if json {
+                while let Some(line) = self.next_line()? {
+                    text.push_str(&line);
+                    text.push('\n');
+                }
+                self.pending.extend(load_manifests(&text));
+                self.done = true;
+                return Ok(());
+            }
+        }
+        self.pending.extend(parse_document(first_line, &text));
+        self.done = true;
+        Ok(())
+    }
+
+    // the next line without its line ending, lone CRs end lines as well
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        while self.lines.is_empty() {
+            let mut bytes = Vec::new();
+            if self.reader.read_until(b'\n', &mut bytes)? == 0 {
+                return Ok(None);
+            }
+            let bytes = match self.line {
+                0 if !self.latin1 => bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(&bytes),
+                _ => &bytes,
+            };
+            let text = self.decode(bytes);
+            let text = text.strip_suffix('\n').unwrap_or(&text);
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            self.lines.extend(text.split('\r').map(String::from));
+        }
+        self.line += 1;
+        Ok(self.lines.pop_front())
+    }
+
+    fn decode(&mut self, bytes: &[u8]) -> String {
+        if !self.latin1 {
+            match std::str::from_utf8(bytes) {
+                Ok(text) => return text.to_string(),
+                Err(err) => {
+                    warn!(
+                        "line {} of the source is not valid UTF-8 ({}), decoding it and the rest as latin-1",
+                        self.line + 1,
+                        err
+                    );
+                    self.latin1 = true;
+                }
+            }
+        }
+        bytes.iter().map(|&byte| byte as char).collect()
+    }
+}
+
+// parses the documents of a yaml stream or of a json object or array of
+// objects, json is re-serialized compactly since yaml-rust rejects tab
+// indentation, documents which fail to parse are returned as errors naming
+// their first line and content
+pub fn load_manifests(source: &str) -> Vec<Result<Document, String>> {
+    let trimmed = source.trim_start();
+    if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(source) {
+            let documents = match json {
+                serde_json::Value::Array(documents) => documents,
+                document => vec![document],
+            };
+            return documents
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, document)| match YamlLoader::load_from_str(&document.to_string()) {
+                    Ok(yamls) => yamls.into_iter().map(|yaml| Ok(Document { yaml, source: None })).collect(),
+                    Err(err) => vec![Err(format!("json document {}: {}", idx, err))],
+                })
+                .collect();
+        }
+    }
+    // documents are parsed one by one to keep the text of each of them and
+    // to continue after a broken one
+    split_documents(source)
+        .into_iter()
+        .flat_map(|(line, text)| parse_document(line, &text))
+        .collect()
+}
+
+// parses the text between two document markers starting at line
+fn parse_document(line: usize, text: &str) -> Vec<Result<Document, String>> {
+    match YamlLoader::load_from_str(text) {
+        Ok(yamls) => yamls
+            .into_iter()
+            .map(|yaml| {
+                Ok(Document {
+                    yaml,
+                    source: Some(text.to_string()),
+                })
+            })
+            .collect(),
+        Err(err) => vec![Err(format!("document at line {}: {}\n{}", line, err, snippet(text)))],
+    }
+}
+
+fn is_marker(line: &str) -> bool {
+    ["---", "..."].iter().any(|marker| {
+        line.starts_with(marker) && line[marker.len()..].chars().next().unwrap_or(' ').is_whitespace()
+    })
+}
+
+// splits a yaml stream at its document markers, the markers are dropped,
+// returns the first line number of each document and its text
+fn split_documents(source: &str) -> Vec<(usize, String)> {
+    let mut documents = vec![(1, String::new())];
+    for (idx, line) in source.lines().enumerate() {
+        if is_marker(line) {
+            documents.push((idx + 2, String::new()));
+        } else {
+            let document = &mut documents.last_mut().unwrap().1;
+            document.push_str(line);
+            document.push('\n');
+        }
+    }
+    documents
+}
+
+// first lines of a document for error messages
+fn snippet(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .take(3)
+        .map(|line| format!("    {}", line))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// returns the items of a kind List document, any other document as is,
+// the items have no source text of their own
+pub fn expand_list(document: Document) -> Vec<Document> {
+    if document.yaml["kind"].as_str() == Some("List") {
+        if let Some(items) = document.yaml["items"].as_vec() {
+            return items
+                .iter()
+                .map(|item| Document {
+                    yaml: item.clone(),
+                    source: None,
+                })
+                .collect();
+        }
+    }
+    vec![document]
+}
+
+// sets metadata.<field> of a manifest
+pub fn set_metadata_field(manifest: &mut Yaml, field: &str, value: &str) {
+    if let Yaml::Hash(metadata) = hash_entry(manifest, "metadata") {
+        set_entry(metadata, Yaml::String(field.to_string()), Yaml::String(value.to_string()));
+    }
+}
+
+// sets metadata.<field>.<key> of a manifest, creating the maps if missing
+pub fn set_metadata_entry(manifest: &mut Yaml, field: &str, key: &str, value: &str) {
+    let entries = hash_entry(hash_entry(manifest, "metadata"), field);
+    if let Yaml::Hash(entries) = entries {
+        set_entry(entries, Yaml::String(key.to_string()), Yaml::String(value.to_string()));
+    }
+}
+
+// sets the value of a key, existing keys keep their position since
+// Hash::insert moves them to the end and reorders the emitted mapping
+pub fn set_entry(hash: &mut Hash, key: Yaml, value: Yaml) {
+    match hash.get_mut(&key) {
+        Some(entry) => *entry = value,
+        None => {
+            hash.insert(key, value);
+        }
+    }
+}
+
+// removes the field at a dotted path, e.g. metadata.managedFields
+pub fn remove_field(manifest: &mut Yaml, path: &str) {
+    let mut keys: Vec<&str> = path.split('.').collect();
+    let last = keys.pop().unwrap();
+    if let Some(Yaml::Hash(hash)) = field_mut(manifest, &keys) {
+        hash.remove(&Yaml::String(last.to_string()));
+    }
+}
+
+// returns the value at the path of mapping keys
+pub fn field_mut<'a>(yaml: &'a mut Yaml, keys: &[&str]) -> Option<&'a mut Yaml> {
+    let mut current = yaml;
+    for key in keys {
+        current = match current {
+            Yaml::Hash(hash) => hash.get_mut(&Yaml::String(key.to_string()))?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+// removes the labels and annotations helm adds to rendered charts from the
+// resource and its pod template, emptied maps are dropped
+pub fn strip_helm_metadata(manifest: &mut Yaml) {
+    if let Some(metadata) = field_mut(manifest, &["spec", "template", "metadata"]) {
+        strip_helm_entries(metadata);
+    }
+    if let Some(metadata) = field_mut(manifest, &["metadata"]) {
+        strip_helm_entries(metadata);
+    }
+}
+
+fn strip_helm_entries(metadata: &mut Yaml) {
+    if let Yaml::Hash(metadata) = metadata {
+        for field in &["labels", "annotations"] {
+            let field = Yaml::String(field.to_string());
+            if let Some(Yaml::Hash(entries)) = metadata.get_mut(&field) {
+                let helm_keys: Vec<Yaml> = entries
+                    .iter()
+                    .filter(|(key, value)| is_helm_entry(key.as_str(), value.as_str()))
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                for key in helm_keys {
+                    entries.remove(&key);
+                }
+                if entries.is_empty() {
+                    metadata.remove(&field);
+                }
+            }
+        }
+    }
+}
+
+fn is_helm_entry(key: Option<&str>, value: Option<&str>) -> bool {
+    match (key, value) {
+        (Some("app.kubernetes.io/managed-by"), Some(value)) => value == "Helm",
+        (Some("helm.sh/chart"), _) => true,
+        (Some(key), _) => key.starts_with("meta.helm.sh/"),
+        _ => false,
+    }
+}
+
+// returns the value of the key in a mapping, inserting an empty mapping if
+// the key is missing or null
+fn hash_entry<'a>(yaml: &'a mut Yaml, key: &str) -> &'a mut Yaml {
+    if let Yaml::Hash(hash) = yaml {
+        let key = Yaml::String(key.to_string());
+        let entry = hash.entry(key).or_insert_with(|| Yaml::Hash(Hash::new()));
+        if let Yaml::Null = entry {
+            *entry = Yaml::Hash(Hash::new());
+        }
+        return entry;
+    }
+    yaml
+}
@@ -0,0 +1,45 @@
+// configs declare the shape they are written in by their apiVersion, configs
+// written before the field existed are v1alpha1
+pub const API_VERSION: &str = "kustomize-upstream.io/v1";
+pub const LEGACY_API_VERSION: &str = "kustomize-upstream.io/v1alpha1";
+
+// upgrade of the config text from one apiVersion to the next, text is
+// rewritten instead of reserialized to keep comments and formatting
+type Migration = (&'static str, &'static str, fn(&str) -> String);
+
+const MIGRATIONS: &[Migration] = &[(LEGACY_API_VERSION, API_VERSION, v1alpha1_to_v1)];
+
+// returns the migrated config text and the applied migrations
+pub fn migrate(config: &str) -> Result<(String, Vec<String>), String> {
+    let mut version = api_version(config)?.unwrap_or_else(|| LEGACY_API_VERSION.to_string());
+    let mut config = config.to_string();
+    let mut applied = Vec::new();
+    while version != API_VERSION {
+        let (from, to, migration) = MIGRATIONS
+            .iter()
+            .find(|(from, _, _)| *from == version)
+            .ok_or_else(|| format!("unknown config apiVersion {}", version))?;
+        config = migration(&config);
+        applied.push(format!("{} -> {}", from, to));
+        version = to.to_string();
+    }
+    Ok((config, applied))
+}
+
+// the apiVersion declared by the config text, None for legacy configs
+pub fn api_version(config: &str) -> Result<Option<String>, String> {
+    let value: serde_yaml::Value = serde_yaml::from_str(config).map_err(|err| err.to_string())?;
+    Ok(value
+        .get("apiVersion")
+        .and_then(|version| version.as_str())
+        .map(String::from))
+}
+
+// declares the apiVersion and replaces the printf style index formatting of
+// the former usage example, which tera can not parse, by the pad3 filter
+fn v1alpha1_to_v1(config: &str) -> String {
+    let config = config
+        .replace("'%03d' % resource.index", "resource.index | pad3")
+        .replace("\"%03d\" % resource.index", "resource.index | pad3");
+    format!("apiVersion: {}\n{}", API_VERSION, config)
+}
@@ -0,0 +1,429 @@
+use crate::{archive, snapshot, Error, Progress};
+use rayon::prelude::*;
+use similar::TextDiff;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+// list of the files written by the last run, relative to the output directory
+const RECORD_FILENAME: &str = ".kustomize-upstream-files";
+
+// writes the generated files, rendered paths are relative to the output
+// directory, by default the working directory
+#[derive(Default)]
+pub struct Output {
+    root: PathBuf,
+    mode: Mode,
+    // overwrite existing files which were not generated by a previous run
+    force: bool,
+    // files written by the previous run according to its record
+    recorded: BTreeSet<PathBuf>,
+    // every file generated so far
+    files: Vec<PathBuf>,
+    // scaffolded files, which belong to the user once they exist
+    scaffolded: BTreeSet<PathBuf>,
+    // generated files which differ from the ones on disk
+    drifted: Vec<PathBuf>,
+    // existing files which were not overwritten as they were not generated
+    refused: Vec<PathBuf>,
+    // content of the generated files in archive mode
+    archived: Vec<(PathBuf, Vec<u8>)>,
+    // packages and manifests of the resources in stdout mode
+    streamed: Vec<(String, String)>,
+    // print a record per file to stdout instead of logging
+    porcelain: bool,
+}
+
+// what happened to a file, printed as a record in porcelain mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Status {
+    Created,
+    Unchanged,
+    Refused,
+    Kept,
+    Stale,
+    Deleted,
+    Archived,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Mode {
+    #[default]
+    Write,
+    // render everything but leave the file system untouched
+    DryRun,
+    // print a unified diff of the generated files against the ones on disk
+    Diff,
+    // only record which generated files differ from the ones on disk
+    Check,
+    // collect the generated files to write them into an archive
+    Archive,
+    // print the manifests of the resources as one yaml stream
+    Stdout,
+    // like DryRun without any messages, for list and explain
+    Inspect,
+}
+
+impl Output {
+    pub fn new(output_dir: Option<&str>, mode: Mode, force: bool, porcelain: bool) -> Output {
+        let mut output = Output {
+            root: output_dir.map(PathBuf::from).unwrap_or_default(),
+            mode,
+            force,
+            porcelain,
+            ..Default::default()
+        };
+        if let Ok(record) = fs::read_to_string(output.path(Path::new(RECORD_FILENAME))) {
+            output.recorded = record.lines().map(|line| output.path(Path::new(line))).collect();
+        }
+        output
+    }
+
+    // collects the generated files like archive mode without looking at the
+    // working directory
+    pub fn in_memory() -> Output {
+        Output {
+            mode: Mode::Archive,
+            ..Default::default()
+        }
+    }
+
+    // location of a rendered path, absolute paths are kept as they are
+    pub fn path(&self, path: &Path) -> PathBuf {
+        self.root.join(path)
+    }
+
+    pub fn write<C: AsRef<[u8]>>(&mut self, path: &Path, content: C) -> Result<(), Error> {
+        let filepath = self.path(path);
+        match self.mode {
+            Mode::Write => {
+                let status = self.write_file(&filepath, content.as_ref())?;
+                self.record(status, filepath);
+                return Ok(());
+            }
+            Mode::DryRun | Mode::Stdout | Mode::Inspect => {}
+            Mode::Archive => self.archived.push((filepath.clone(), content.as_ref().to_vec())),
+            Mode::Diff => print_diff(&filepath, content.as_ref()),
+            Mode::Check => {
+                if fs::read(&filepath).ok().as_deref() != Some(content.as_ref()) {
+                    self.drifted.push(filepath.clone());
+                }
+            }
+        }
+        self.files.push(filepath);
+        Ok(())
+    }
+
+    // writes files like write, in write mode the files are compared with the
+    // ones on disk and written in parallel, the results and messages are in
+    // the order of the files
+    pub fn write_all<C: AsRef<[u8]> + Sync>(
+        &mut self,
+        files: &[(PathBuf, C)],
+        progress: &Progress,
+    ) -> Vec<Result<(), Error>> {
+        if self.mode != Mode::Write {
+            return files.iter().map(|(path, content)| self.write(path, content)).collect();
+        }
+        let step = progress.count("write", "files", Some(files.len() as u64));
+        let written: Vec<Result<Status, Error>> = files
+            .par_iter()
+            .map(|(path, content)| {
+                let status = self.write_file(&self.path(path), content.as_ref());
+                step.inc(1);
+                status
+            })
+            .collect();
+        files
+            .iter()
+            .zip(written)
+            .map(|((path, _), status)| {
+                let filepath = self.path(path);
+                self.record(status?, filepath);
+                Ok(())
+            })
+            .collect()
+    }
+
+    // writes a file in write mode unless it is unchanged or belongs to the user
+    fn write_file(&self, filepath: &Path, content: &[u8]) -> Result<Status, Error> {
+        // identical files are left alone to keep their mtime stable
+        if fs::read(filepath).ok().as_deref() == Some(content) {
+            return Ok(Status::Unchanged);
+        }
+        if !self.force && filepath.exists() && !self.owns(filepath) {
+            return Ok(Status::Refused);
+        }
+        if let Some(dir) = filepath.parent() {
+            fs::create_dir_all(dir).map_err(Error::file("create", dir))?;
+        }
+        fs::write(filepath, content).map_err(Error::file("write", filepath))?;
+        Ok(Status::Created)
+    }
+
+    // reports what happened to a file in write mode, refused files are not
+    // generated by this run
+    fn record(&mut self, status: Status, filepath: PathBuf) {
+        self.status(status, &filepath);
+        match status {
+            Status::Refused => self.refused.push(filepath),
+            _ => self.files.push(filepath),
+        }
+    }
+
+    // true if the file was generated by this or the previous run
+    fn owns(&self, filepath: &Path) -> bool {
+        self.recorded.contains(filepath) || self.files.iter().any(|file| file == filepath)
+    }
+
+    // writes a file only once, existing files belong to the user
+    pub fn scaffold<C: AsRef<[u8]>>(&mut self, path: &Path, content: C) -> Result<(), Error> {
+        let filepath = self.path(path);
+        self.scaffolded.insert(filepath.clone());
+        if filepath.exists() && self.mode != Mode::Archive {
+            if self.mode == Mode::Write || self.mode == Mode::DryRun {
+                self.status(Status::Kept, &filepath);
+            }
+            self.files.push(filepath);
+            Ok(())
+        } else {
+            self.write(path, content)
+        }
+    }
+
+    // files written by the previous run which were not written by this one,
+    // e.g. resources removed upstream, paths starting with one of keep are
+    // left out, files the previous run did not write belong to the user
+    pub fn stale_files(&self, keep: &[String]) -> Vec<&PathBuf> {
+        let files: BTreeSet<&PathBuf> = self.files.iter().collect();
+        self.recorded
+            .iter()
+            .filter(|file| !files.contains(file) && file.is_file())
+            .filter(|file| {
+                let relative = file.strip_prefix(&self.root).unwrap_or(file);
+                relative != Path::new(RECORD_FILENAME) && !keep.iter().any(|prefix| relative.starts_with(prefix))
+            })
+            .collect()
+    }
+
+    pub fn print_removed(&self, keep: &[String]) {
+        for path in self.stale_files(keep) {
+            let old = fs::read(path).unwrap_or_default();
+            let old = String::from_utf8_lossy(&old);
+            let diff = TextDiff::from_lines(old.as_ref(), "");
+            let old_header = format!("a/{}", path.display());
+            print!("{}", diff.unified_diff().header(&old_header, "/dev/null"));
+        }
+    }
+
+    // files written by the previous run according to its record
+    pub fn recorded(&self) -> &BTreeSet<PathBuf> {
+        &self.recorded
+    }
+
+    // every file generated so far
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    // existing files which were left untouched as they were not generated
+    // by a previous run
+    pub fn refused(&self) -> &[PathBuf] {
+        &self.refused
+    }
+
+    // records the written files for the next run, together with the files of
+    // earlier runs which were not pruned, scaffolded files are left out so
+    // they are never pruned
+    pub fn save_record(&self) -> Result<(), Error> {
+        let previous: Vec<&PathBuf> = self.recorded.iter().filter(|file| file.is_file()).collect();
+        let mut lines: Vec<String> = self
+            .files
+            .iter()
+            .chain(previous)
+            .filter(|file| !self.scaffolded.contains(*file))
+            .map(|file| file.strip_prefix(&self.root).unwrap_or(file).display().to_string())
+            .collect();
+        lines.sort();
+        lines.dedup();
+        let record = self.path(Path::new(RECORD_FILENAME));
+        fs::write(&record, lines.join("\n") + "\n").map_err(Error::file("write", &record))
+    }
+
+    // deletes the files written by the previous run which were not written
+    // by this one, paths starting with one of keep are never deleted, asks
+    // for confirmation on a terminal unless confirmed
+    pub fn prune(&self, keep: &[String], confirmed: bool) -> Result<(), Error> {
+        let stale = self.stale_files(keep);
+        if stale.is_empty() {
+            return Ok(());
+        }
+        for file in &stale {
+            self.status(Status::Stale, file);
+        }
+        if !confirmed {
+            if !std::io::stdin().is_terminal() {
+                warn!("not pruning {} files without --yes", stale.len());
+                return Ok(());
+            }
+            print!("delete {} files? [y/N] ", stale.len());
+            let mut answer = String::new();
+            // an unreadable answer is no
+            let _ = std::io::stdout().flush();
+            let _ = std::io::stdin().lock().read_line(&mut answer);
+            if answer.trim() != "y" {
+                return Ok(());
+            }
+        }
+        for file in &stale {
+            self.status(Status::Deleted, file);
+            fs::remove_file(file).map_err(Error::file("delete", *file))?;
+        }
+        Ok(())
+    }
+
+    // records the manifest of a resource written to a file of a package
+    pub fn stream(&mut self, package: &str, manifest: &str) {
+        if self.mode == Mode::Stdout {
+            self.streamed.push((package.to_string(), manifest.to_string()));
+        }
+    }
+
+    // prints the manifests of the resources in the given packages, all if
+    // none are given, as one multi-document yaml stream
+    pub fn print_stream(&self, packages: &[String]) {
+        for (package, manifest) in &self.streamed {
+            if !packages.is_empty() && !packages.contains(package) {
+                continue;
+            }
+            if !manifest.starts_with("---") {
+                println!("---");
+            }
+            print!("{}", manifest);
+        }
+    }
+
+    // writes the files collected in archive mode
+    pub fn write_archive(&self, path: &Path) -> Result<(), Error> {
+        archive::write(path, &self.archived).map_err(Error::file("write", path))?;
+        match self.porcelain {
+            true => self.status(Status::Archived, path),
+            false => info!("create archive: {} ({} files)", path.display(), self.archived.len()),
+        }
+        Ok(())
+    }
+
+    // the files collected in archive mode
+    pub fn into_files(self) -> Vec<(PathBuf, Vec<u8>)> {
+        self.archived
+    }
+
+    // writes the files collected in archive mode into a snapshot directory
+    pub fn record_snapshot(&self, dir: &Path) -> Result<(), Error> {
+        snapshot::record(dir, &self.snapshot_files(), self.force)?;
+        info!("record snapshot: {} ({} files)", dir.display(), self.archived.len());
+        Ok(())
+    }
+
+    // compares the files collected in archive mode with a snapshot, printing
+    // a diff per differing file, empty if they match
+    pub fn verify_snapshot(&self, dir: &Path) -> Result<Vec<String>, Error> {
+        snapshot::verify(dir, &self.snapshot_files())
+    }
+
+    // the collected files with paths relative to the output directory
+    fn snapshot_files(&self) -> Vec<(PathBuf, &[u8])> {
+        self.archived
+            .iter()
+            .map(|(path, content)| {
+                let relative = path.strip_prefix(&self.root).unwrap_or(path);
+                (archive::entry_path(relative), content.as_slice())
+            })
+            .collect()
+    }
+
+    // prints the record of a file in porcelain mode, logs it otherwise
+    fn status(&self, status: Status, filepath: &Path) {
+        if self.porcelain {
+            let record = match status {
+                Status::Created => "CREATED",
+                Status::Unchanged => "UNCHANGED",
+                Status::Refused => "REFUSED",
+                Status::Kept => "KEPT",
+                Status::Stale => "STALE",
+                Status::Deleted => "DELETED",
+                Status::Archived => "ARCHIVED",
+            };
+            println!("{} {}", record, filepath.display());
+            return;
+        }
+        match status {
+            Status::Created => info!("create file: {}", filepath.display()),
+            Status::Unchanged => info!("unchanged file: {}", filepath.display()),
+            Status::Refused => warn!("refuse to overwrite file: {}", filepath.display()),
+            Status::Kept => info!("keep file: {}", filepath.display()),
+            Status::Stale => info!("stale file: {}", filepath.display()),
+            Status::Deleted => info!("delete file: {}", filepath.display()),
+            Status::Archived => info!("create archive: {}", filepath.display()),
+        }
+    }
+
+    // new or changed generated files and stale files, empty if the files on
+    // disk are up to date
+    pub fn drift(&self, keep: &[String]) -> Vec<String> {
+        let mut drift: Vec<String> = self
+            .drifted
+            .iter()
+            .map(|path| {
+                let state = if path.exists() { "changed" } else { "new" };
+                format!("{}: {}", state, path.display())
+            })
+            .collect();
+        for path in self.stale_files(keep) {
+            drift.push(format!("stale: {}", path.display()));
+        }
+        drift
+    }
+
+    // prints the files which would have been written grouped by the
+    // directories of the packages, with paths relative to the package
+    pub fn print_plan(&self, packages: &[(String, PathBuf)]) {
+        let mut remaining: Vec<&PathBuf> = self.files.iter().collect();
+        for (name, path) in packages {
+            let path = self.path(path);
+            println!("package {} ({})", name, path.display());
+            remaining.retain(|file| match file.strip_prefix(&path) {
+                Ok(relative) => {
+                    println!("  {}", relative.display());
+                    false
+                }
+                Err(_) => true,
+            });
+        }
+        if !remaining.is_empty() {
+            println!("other files");
+            for file in remaining {
+                println!("  {}", file.display());
+            }
+        }
+    }
+}
+
+// prints the unified diff of a file on disk, which may not exist, and its
+// new content, nothing if they are equal
+fn print_diff(filepath: &Path, content: &[u8]) {
+    let (old, old_header) = match fs::read(filepath) {
+        Ok(old) => (old, format!("a/{}", filepath.display())),
+        Err(_) => (Vec::new(), "/dev/null".to_string()),
+    };
+    if old == content {
+        return;
+    }
+    let old = String::from_utf8_lossy(&old);
+    let new = String::from_utf8_lossy(content);
+    let diff = TextDiff::from_lines(old.as_ref(), new.as_ref());
+    let new_header = format!("b/{}", filepath.display());
+    print!("{}", diff.unified_diff().header(&old_header, &new_header));
+}
@@ -0,0 +1,96 @@
+use serde_json::Value;
+
+// applies a --set assignment like Top.version=1.15.1 or
+// SplitRules[0].packageName=crds to a config, values replacing strings stay
+// strings, other values are parsed as yaml, e.g. CRDsFirst=true
+pub fn apply(config: &mut Value, assignment: &str) -> Result<(), String> {
+    let (path, value) = assignment
+        .split_once('=')
+        .ok_or_else(|| format!("invalid --set {}, expected <path>=<value>", assignment))?;
+    let keys = keys(path);
+    if keys.iter().any(|key| key.is_empty()) {
+        return Err(format!("invalid --set path {}", path));
+    }
+    let mut current = config;
+    for key in keys {
+        current = match current {
+            Value::Array(items) => {
+                let idx: usize = key
+                    .parse()
+                    .map_err(|_| format!("--set {}: {} is not an index", path, key))?;
+                items
+                    .get_mut(idx)
+                    .ok_or_else(|| format!("--set {}: index {} is out of range", path, idx))?
+            }
+            Value::Object(fields) => fields.entry(key).or_insert(Value::Null),
+            Value::Null => {
+                *current = Value::Object(Default::default());
+                current.as_object_mut().unwrap().entry(key).or_insert(Value::Null)
+            }
+            _ => return Err(format!("--set {}: {} is not a mapping", path, key)),
+        };
+    }
+    *current = match current {
+        Value::String(_) => Value::String(value.to_string()),
+        _ => serde_yaml::from_str(value).unwrap_or_else(|_| Value::String(value.to_string())),
+    };
+    Ok(())
+}
+
+// fails if the path of an applied assignment is not part of the config,
+// unknown fields are dropped when deserializing
+pub fn check(config: &Value, assignment: &str) -> Result<(), String> {
+    let path = assignment.split_once('=').map_or(assignment, |(path, _)| path);
+    let mut current = config;
+    for key in keys(path) {
+        current = match current {
+            Value::Array(items) => key.parse::<usize>().ok().and_then(|idx| items.get(idx)),
+            Value::Object(fields) => fields.get(&key),
+            _ => None,
+        }
+        .ok_or_else(|| format!("--set {}: unknown config field {}", path, key))?;
+    }
+    Ok(())
+}
+
+// splits a path at dots and brackets, a[0].b and a.0.b are the same
+fn keys(path: &str) -> Vec<String> {
+    path.replace('[', ".")
+        .replace(']', "")
+        .split('.')
+        .map(String::from)
+        .collect()
+}
+
+// lays the named entry of the Profiles section over the config, fields of the
+// profile replace those of the config, mappings are merged recursively
+pub fn apply_profile(config: &mut Value, name: &str) -> Result<(), String> {
+    let profiles = config.get("Profiles").and_then(Value::as_object);
+    let profile = match profiles.and_then(|profiles| profiles.get(name)) {
+        Some(profile) => profile.clone(),
+        None => {
+            let known: Vec<&str> = profiles
+                .map(|profiles| profiles.keys().map(String::as_str).collect())
+                .unwrap_or_default();
+            return Err(format!("unknown profile {}, the config defines [{}]", name, known.join(", ")));
+        }
+    };
+    overlay(config, profile);
+    Ok(())
+}
+
+fn overlay(config: &mut Value, profile: Value) {
+    match (config, profile) {
+        (Value::Object(fields), Value::Object(profile_fields)) => {
+            for (key, value) in profile_fields {
+                match fields.get_mut(&key) {
+                    Some(field) => overlay(field, value),
+                    None => {
+                        fields.insert(key, value);
+                    }
+                }
+            }
+        }
+        (config, profile) => *config = profile,
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{manifest, Matcher};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use yaml_rust::yaml::Hash;
+use yaml_rust::{Yaml, YamlLoader};
+
+// patch merged into matching manifests before they are classified and written
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PatchRule {
+    #[serde(default)]
+    pub matcher: Matcher,
+    // strategic merge patch, either inline yaml or a yaml string
+    #[schemars(with = "Option<serde_json::Value>")]
+    patch: Option<serde_yaml::Value>,
+    // RFC 6902 operations applied after the strategic merge patch
+    #[serde(default)]
+    jsonPatch: Vec<JsonPatchOp>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum JsonPatchOp {
+    Add {
+        path: String,
+        #[schemars(with = "serde_json::Value")]
+        value: serde_yaml::Value,
+    },
+    Remove { path: String },
+    Replace {
+        path: String,
+        #[schemars(with = "serde_json::Value")]
+        value: serde_yaml::Value,
+    },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test {
+        path: String,
+        #[schemars(with = "serde_json::Value")]
+        value: serde_yaml::Value,
+    },
+}
+
+impl PatchRule {
+    pub fn apply(&self, manifest: &mut Yaml) -> Result<(), String> {
+        if let Some(patch) = &self.patch {
+            strategic_merge(manifest, &to_yaml(patch)?);
+        }
+        if !self.jsonPatch.is_empty() {
+            // the operations are atomic, a failing one leaves the manifest untouched
+            let mut patched = manifest.clone();
+            for op in &self.jsonPatch {
+                op.apply(&mut patched)?;
+            }
+            *manifest = patched;
+        }
+        Ok(())
+    }
+
+    // the patch must parse, a yaml string is only parsed when it is applied
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(patch) = &self.patch {
+            to_yaml(patch)?;
+        }
+        for op in &self.jsonPatch {
+            op.check()?;
+        }
+        Ok(())
+    }
+}
+
+impl JsonPatchOp {
+    fn apply(&self, doc: &mut Yaml) -> Result<(), String> {
+        match self {
+            JsonPatchOp::Add { path, value } => add(doc, path, to_yaml_value(value)?),
+            JsonPatchOp::Remove { path } => remove(doc, path).map(|_| ()),
+            JsonPatchOp::Replace { path, value } => replace(doc, path, to_yaml_value(value)?),
+            JsonPatchOp::Move { from, path } => {
+                let value = remove(doc, from)?;
+                add(doc, path, value)
+            }
+            JsonPatchOp::Copy { from, path } => {
+                let value = pointer(doc, from)
+                    .cloned()
+                    .ok_or_else(|| format!("path {} does not exist", from))?;
+                add(doc, path, value)
+            }
+            JsonPatchOp::Test { path, value } => {
+                if pointer(doc, path) == Some(&to_yaml_value(value)?) {
+                    Ok(())
+                } else {
+                    Err(format!("test of {} failed", path))
+                }
+            }
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        match self {
+            JsonPatchOp::Add { path, value } | JsonPatchOp::Replace { path, value } | JsonPatchOp::Test { path, value } => {
+                tokens(path)?;
+                to_yaml_value(value).map(|_| ())
+            }
+            JsonPatchOp::Remove { path } => tokens(path).map(|_| ()),
+            JsonPatchOp::Move { from, path } | JsonPatchOp::Copy { from, path } => {
+                tokens(from)?;
+                tokens(path).map(|_| ())
+            }
+        }
+    }
+}
+
+// splits a json pointer into its unescaped reference tokens
+fn tokens(path: &str) -> Result<Vec<String>, String> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !path.starts_with('/') {
+        return Err(format!("invalid json pointer {}", path));
+    }
+    Ok(path[1..]
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+fn pointer<'a>(doc: &'a Yaml, path: &str) -> Option<&'a Yaml> {
+    let mut current = doc;
+    for token in tokens(path).ok()? {
+        current = match current {
+            Yaml::Hash(hash) => hash.get(&Yaml::String(token))?,
+            Yaml::Array(array) => array.get(token.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+// returns the container addressed by all but the last token and the last token
+fn parent<'a>(doc: &'a mut Yaml, path: &str) -> Result<(&'a mut Yaml, String), String> {
+    let mut tokens = tokens(path)?;
+    let last = tokens
+        .pop()
+        .ok_or_else(|| "the document root can not be patched".to_string())?;
+    let mut current = doc;
+    for token in tokens {
+        current = match current {
+            Yaml::Hash(hash) => hash.get_mut(&Yaml::String(token)),
+            Yaml::Array(array) => token.parse::<usize>().ok().and_then(move |idx| array.get_mut(idx)),
+            _ => None,
+        }
+        .ok_or_else(|| format!("path {} does not exist", path))?;
+    }
+    Ok((current, last))
+}
+
+fn add(doc: &mut Yaml, path: &str, value: Yaml) -> Result<(), String> {
+    match parent(doc, path)? {
+        (Yaml::Hash(hash), key) => manifest::set_entry(hash, Yaml::String(key), value),
+        (Yaml::Array(array), token) if token == "-" => array.push(value),
+        (Yaml::Array(array), token) => match token.parse::<usize>() {
+            Ok(idx) if idx <= array.len() => array.insert(idx, value),
+            _ => return Err(format!("invalid index in {}", path)),
+        },
+        _ => return Err(format!("path {} does not exist", path)),
+    }
+    Ok(())
+}
+
+// replaces an existing value in place, keeping the key order
+fn replace(doc: &mut Yaml, path: &str, value: Yaml) -> Result<(), String> {
+    let entry = match parent(doc, path)? {
+        (Yaml::Hash(hash), key) => hash.get_mut(&Yaml::String(key)),
+        (Yaml::Array(array), token) => match token.parse::<usize>() {
+            Ok(idx) => array.get_mut(idx),
+            Err(_) => None,
+        },
+        _ => None,
+    };
+    *entry.ok_or_else(|| format!("path {} does not exist", path))? = value;
+    Ok(())
+}
+
+fn remove(doc: &mut Yaml, path: &str) -> Result<Yaml, String> {
+    let removed = match parent(doc, path)? {
+        (Yaml::Hash(hash), key) => hash.remove(&Yaml::String(key)),
+        (Yaml::Array(array), token) => match token.parse::<usize>() {
+            Ok(idx) if idx < array.len() => Some(array.remove(idx)),
+            _ => None,
+        },
+        _ => None,
+    };
+    removed.ok_or_else(|| format!("path {} does not exist", path))
+}
+
+// converts a config value into a yaml-rust value
+fn to_yaml_value(value: &serde_yaml::Value) -> Result<Yaml, String> {
+    let source = serde_yaml::to_string(value).map_err(|err| err.to_string())?;
+    load_yaml(&source)
+}
+
+// converts a config value into a yaml-rust document, strings are parsed as yaml
+fn to_yaml(value: &serde_yaml::Value) -> Result<Yaml, String> {
+    match value {
+        serde_yaml::Value::String(source) => load_yaml(source),
+        value => to_yaml_value(value),
+    }
+}
+
+fn load_yaml(source: &str) -> Result<Yaml, String> {
+    let documents = YamlLoader::load_from_str(source).map_err(|err| format!("invalid patch: {}", err))?;
+    Ok(documents.into_iter().next().unwrap_or(Yaml::Null))
+}
+
+// merges a patch into a manifest the way kubectl does for the common cases:
+// mappings merge recursively, null removes a key, lists of mappings merge
+// items by name and `$patch: delete` removes a list item, other lists and
+// scalars are replaced
+pub fn strategic_merge(target: &mut Yaml, patch: &Yaml) {
+    match (target, patch) {
+        (Yaml::Hash(target), Yaml::Hash(patch)) => {
+            if patch_directive(patch) == Some("replace") {
+                *target = without_directive(patch);
+                return;
+            }
+            for (key, value) in patch {
+                if let Yaml::Null = value {
+                    target.remove(key);
+                } else if let Some(entry) = target.get_mut(key) {
+                    strategic_merge(entry, value);
+                } else {
+                    target.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        (Yaml::Array(target), Yaml::Array(patch)) if is_named_list(patch) => {
+            for item in patch {
+                let name = &item["name"];
+                let position = target.iter().position(|entry| &entry["name"] == name);
+                let delete = item.as_hash().and_then(patch_directive) == Some("delete");
+                match (position, delete) {
+                    (Some(position), true) => {
+                        target.remove(position);
+                    }
+                    (Some(position), false) => strategic_merge(&mut target[position], item),
+                    (None, false) => target.push(item.clone()),
+                    (None, true) => {}
+                }
+            }
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+fn is_named_list(list: &[Yaml]) -> bool {
+    !list.is_empty() && list.iter().all(|item| item["name"].as_str().is_some())
+}
+
+fn patch_directive(hash: &Hash) -> Option<&str> {
+    hash.get(&Yaml::String("$patch".to_string()))
+        .and_then(|directive| directive.as_str())
+}
+
+fn without_directive(hash: &Hash) -> Hash {
+    let mut hash = hash.clone();
+    hash.remove(&Yaml::String("$patch".to_string()));
+    hash
+}
@@ -0,0 +1,148 @@
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+// how often the progress is logged when stderr is no terminal
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Where the progress of the long steps of a run is shown, e.g. downloading
+/// the upstreams and writing their resources, nothing by default.
+#[derive(Clone, Default)]
+pub struct Progress {
+    // bars drawn on stderr, None if the progress is logged or not shown
+    bars: Option<MultiProgress>,
+    log: bool,
+}
+
+impl Progress {
+    /// Draws progress bars on stderr if it is a terminal, otherwise logs the
+    /// progress every few seconds.
+    pub fn stderr() -> Progress {
+        match std::io::stderr().is_terminal() {
+            true => Progress {
+                bars: Some(MultiProgress::new()),
+                log: false,
+            },
+            false => Progress { bars: None, log: true },
+        }
+    }
+
+    /// Runs `f` with the bars cleared from stderr, e.g. to log a message
+    /// without tearing them.
+    pub fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        match &self.bars {
+            Some(bars) => bars.suspend(f),
+            None => f(),
+        }
+    }
+
+    // a step counting bytes, e.g. downloading, the total grows as the sizes
+    // become known
+    pub(crate) fn bytes(&self, step: &str) -> Step {
+        self.step(step, None, None)
+    }
+
+    // a step counting things, e.g. files, the total is unknown if None
+    pub(crate) fn count(&self, step: &str, unit: &'static str, total: Option<u64>) -> Step {
+        self.step(step, Some(unit), total)
+    }
+
+    fn step(&self, step: &str, unit: Option<&'static str>, total: Option<u64>) -> Step {
+        let step = Step {
+            bar: ProgressBar::hidden().with_message(step.to_string()),
+            step: step.to_string(),
+            unit,
+            unknown_total: AtomicBool::new(false),
+            log: self.log,
+            logged: Mutex::new(Instant::now()),
+        };
+        match total {
+            Some(total) => step.bar.set_length(total),
+            None => step.bar.unset_length(),
+        }
+        step.set_style();
+        // the bar is styled before it is drawn
+        if let Some(bars) = &self.bars {
+            bars.add(step.bar.clone());
+            step.bar.enable_steady_tick(Duration::from_millis(100));
+        }
+        step
+    }
+}
+
+// a running step, its bar is cleared when it is dropped
+pub(crate) struct Step {
+    bar: ProgressBar,
+    step: String,
+    // None for bytes
+    unit: Option<&'static str>,
+    // a part of unknown size was added, the total stays unknown
+    unknown_total: AtomicBool,
+    log: bool,
+    // when the progress was logged last
+    logged: Mutex<Instant>,
+}
+
+impl Step {
+    pub fn inc(&self, delta: u64) {
+        self.bar.inc(delta);
+        if !self.log {
+            return;
+        }
+        let mut logged = self.logged.lock().unwrap();
+        if logged.elapsed() >= LOG_INTERVAL {
+            *logged = Instant::now();
+            info!("{}: {}", self.step, self.describe());
+        }
+    }
+
+    // adds to the total, a part of unknown size leaves the total unknown
+    pub fn inc_length(&self, delta: Option<u64>) {
+        if self.unknown_total.load(Ordering::Relaxed) {
+            return;
+        }
+        match (delta, self.bar.length()) {
+            (Some(delta), Some(_)) => self.bar.inc_length(delta),
+            (Some(delta), None) => {
+                self.bar.set_length(delta);
+                self.set_style();
+            }
+            (None, _) => {
+                self.unknown_total.store(true, Ordering::Relaxed);
+                self.bar.unset_length();
+                self.set_style();
+            }
+        }
+    }
+
+    // e.g. 3.5 MiB of 12.0 MiB or 300 of 1200 files
+    fn describe(&self) -> String {
+        let position = self.bar.position();
+        match (self.unit, self.bar.length()) {
+            (None, Some(length)) => format!("{} of {}", HumanBytes(position), HumanBytes(length)),
+            (None, None) => HumanBytes(position).to_string(),
+            (Some(unit), Some(length)) => format!("{} of {} {}", position, length, unit),
+            (Some(unit), None) => format!("{} {}", position, unit),
+        }
+    }
+
+    fn set_style(&self) {
+        let template = match (self.unit, self.bar.length()) {
+            (None, Some(_)) => "{spinner} {msg} {bytes}/{total_bytes} [{bar:30}] {eta}".to_string(),
+            (None, None) => "{spinner} {msg} {bytes}".to_string(),
+            (Some(unit), Some(_)) => format!("{{spinner}} {{msg}} {{pos}}/{{len}} {} [{{bar:30}}]", unit),
+            (Some(unit), None) => format!("{{spinner}} {{msg}} {{pos}} {}", unit),
+        };
+        let style = ProgressStyle::with_template(&template).expect("the progress templates are valid");
+        self.bar.set_style(style.progress_chars("=> "));
+    }
+}
+
+impl Drop for Step {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{Matcher, Resource};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// kustomize replacement rendered into every package which contains the
+// source and at least one of the targets
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Replacement {
+    source: Source,
+    targets: Vec<Target>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct Source {
+    #[serde(flatten)]
+    selector: Selector,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fieldPath: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<serde_json::Value>")]
+    options: Option<serde_yaml::Value>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct Target {
+    select: Selector,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reject: Option<Vec<Selector>>,
+    fieldPaths: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<serde_json::Value>")]
+    options: Option<serde_yaml::Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Default)]
+struct Selector {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+}
+
+impl Selector {
+    fn selects(&self, resources: &[Resource]) -> bool {
+        let matcher = Matcher {
+            kind: self.kind.clone(),
+            name: self.name.clone(),
+            namespace: self.namespace.clone(),
+            scope: None,
+        };
+        resources.iter().any(|resource| matcher.do_match(resource))
+    }
+}
+
+// returns the replacements affecting the resources of a package, with the
+// targets reduced to those present in the package
+pub fn package_replacements(replacements: &[Replacement], resources: &[Resource]) -> Vec<Replacement> {
+    let mut package_replacements = Vec::new();
+    for replacement in replacements {
+        if !replacement.source.selector.selects(resources) {
+            continue;
+        }
+        let targets: Vec<Target> = replacement
+            .targets
+            .iter()
+            .filter(|target| target.select.selects(resources))
+            .cloned()
+            .collect();
+        if !targets.is_empty() {
+            package_replacements.push(Replacement {
+                source: replacement.source.clone(),
+                targets,
+            });
+        }
+    }
+    package_replacements
+}
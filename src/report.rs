@@ -0,0 +1,292 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// machine readable summary of a run, written by --report and printed by list
+#[derive(Serialize)]
+pub struct Report {
+    name: String,
+    source: String,
+    version: String,
+    packages: Vec<PackageEntry>,
+}
+
+#[derive(Serialize)]
+struct PackageEntry {
+    name: String,
+    // path of the package descriptor
+    descriptor: Option<String>,
+    resources: Vec<ResourceEntry>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize)]
+pub struct ResourceEntry {
+    pub kind: String,
+    pub name: String,
+    pub namespace: Option<String>,
+    // index of the split rule which placed the resource, none for the
+    // default package
+    pub splitRule: Option<usize>,
+    pub path: String,
+    // apiVersion of the upstream manifest if UpgradeApiVersions rewrote it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upgradedFrom: Option<String>,
+    // sha256 of the written manifest
+    pub sha256: String,
+    // written manifest without provenance annotations, only kept for the
+    // changelog and diff-versions
+    #[serde(skip)]
+    pub manifest: Option<String>,
+}
+
+impl Report {
+    pub fn new(name: String, source: String, version: String) -> Report {
+        Report {
+            name,
+            source,
+            version,
+            packages: Vec::new(),
+        }
+    }
+
+    fn package(&mut self, name: &str) -> &mut PackageEntry {
+        let idx = match self.packages.iter().position(|package| package.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.packages.push(PackageEntry {
+                    name: name.to_string(),
+                    descriptor: None,
+                    resources: Vec::new(),
+                });
+                self.packages.len() - 1
+            }
+        };
+        &mut self.packages[idx]
+    }
+
+    pub fn add_resource(&mut self, package: &str, resource: ResourceEntry) {
+        self.package(package).resources.push(resource);
+    }
+
+    pub fn set_descriptor(&mut self, package: &str, descriptor: &Path) {
+        self.package(package).descriptor = Some(descriptor.display().to_string());
+    }
+
+    pub fn render(&mut self, format: Format) -> String {
+        self.sort();
+        match format {
+            Format::Json => serde_json::to_string_pretty(self).unwrap() + "\n",
+            Format::Yaml => serde_yaml::to_string(self).unwrap(),
+            Format::Table => self.table(),
+        }
+    }
+
+    pub fn resources(&self) -> impl Iterator<Item = &ResourceEntry> {
+        self.packages.iter().flat_map(|package| package.resources.iter())
+    }
+
+    fn sort(&mut self) {
+        self.packages.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    // one row per resource with aligned columns
+    fn table(&self) -> String {
+        let mut rows = vec![["PACKAGE", "KIND", "NAMESPACE", "NAME", "PATH"].map(String::from).to_vec()];
+        for package in &self.packages {
+            for resource in &package.resources {
+                rows.push(vec![
+                    package.name.clone(),
+                    resource.kind.clone(),
+                    resource.namespace.clone().unwrap_or_default(),
+                    resource.name.clone(),
+                    resource.path.clone(),
+                ]);
+            }
+        }
+        align(&rows)
+    }
+}
+
+// compares the resources of the upstreams generated for several versions,
+// one table per upstream listing the resources whose manifests differ
+// between the versions with the start of their sha256, - where missing,
+// empty if no upstream has several versions
+pub fn version_differences(reports: &[Report]) -> String {
+    let mut names: Vec<&str> = reports.iter().map(|report| report.name.as_str()).collect();
+    names.dedup();
+    let mut differences = String::new();
+    for name in names {
+        let versions: Vec<&Report> = reports.iter().filter(|report| report.name == name).collect();
+        if versions.len() < 2 {
+            continue;
+        }
+        let mut digests: BTreeMap<[&str; 4], Vec<String>> = BTreeMap::new();
+        for (idx, report) in versions.iter().enumerate() {
+            for package in &report.packages {
+                for resource in &package.resources {
+                    let key = [
+                        package.name.as_str(),
+                        resource.kind.as_str(),
+                        resource.namespace.as_deref().unwrap_or_default(),
+                        resource.name.as_str(),
+                    ];
+                    let row = digests.entry(key).or_insert_with(|| vec!["-".to_string(); versions.len()]);
+                    row[idx] = resource.sha256.chars().take(12).collect();
+                }
+            }
+        }
+        let mut header: Vec<String> = ["PACKAGE", "KIND", "NAMESPACE", "NAME"].map(String::from).to_vec();
+        header.extend(versions.iter().map(|report| report.version.clone()));
+        let mut rows = vec![header];
+        for (key, row) in digests {
+            if row.iter().any(|digest| digest != &row[0]) {
+                rows.push(key.iter().map(|cell| cell.to_string()).chain(row).collect());
+            }
+        }
+        differences.push_str(&format!(
+            "\n{} resources of {} differ between versions\n",
+            rows.len() - 1,
+            name
+        ));
+        if rows.len() > 1 {
+            differences.push_str(&align(&rows));
+        }
+    }
+    differences
+}
+
+// resources added, removed and changed from the old to the new report per
+// package, resources are changed if their manifests differ in more than the
+// version, e.g. in a label holding it, and the provenance annotations
+pub fn compare(old: &Report, new: &Report) -> String {
+    let old_resources = resources_by_package(old);
+    let new_resources = resources_by_package(new);
+    let mut packages: Vec<&str> = old_resources.keys().chain(new_resources.keys()).copied().collect();
+    packages.sort();
+    packages.dedup();
+    let empty = BTreeMap::new();
+    let mut comparison = format!("{} {} → {}\n", new.name, old.version, new.version);
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for package in packages {
+        let old_package = old_resources.get(package).unwrap_or(&empty);
+        let new_package = new_resources.get(package).unwrap_or(&empty);
+        let mut lines = Vec::new();
+        for (resource, manifest) in new_package {
+            match old_package.get(resource) {
+                None => {
+                    lines.push(format!("  + {}", resource));
+                    added += 1;
+                }
+                Some(old_manifest)
+                    if old_manifest.replace(&old.version, "VERSION") != manifest.replace(&new.version, "VERSION") =>
+                {
+                    lines.push(format!("  ~ {}", resource));
+                    changed += 1;
+                }
+                Some(_) => {}
+            }
+        }
+        for resource in old_package.keys().filter(|resource| !new_package.contains_key(*resource)) {
+            lines.push(format!("  - {}", resource));
+            removed += 1;
+        }
+        if lines.is_empty() {
+            continue;
+        }
+        let state = match (old_resources.contains_key(package), new_resources.contains_key(package)) {
+            (false, _) => " (new)",
+            (_, false) => " (removed)",
+            _ => "",
+        };
+        comparison.push_str(&format!("package {}{}\n", package, state));
+        lines.sort_by(|a, b| a[4..].cmp(&b[4..]));
+        for line in lines {
+            comparison.push_str(&line);
+            comparison.push('\n');
+        }
+    }
+    comparison.push_str(&format!(
+        "{} added, {} removed, {} changed resources\n",
+        added, removed, changed
+    ));
+    comparison
+}
+
+// manifests of the resources by package and kind namespace/name
+fn resources_by_package(report: &Report) -> BTreeMap<&str, BTreeMap<String, &str>> {
+    let mut packages: BTreeMap<&str, BTreeMap<String, &str>> = BTreeMap::new();
+    for package in &report.packages {
+        let resources = packages.entry(package.name.as_str()).or_default();
+        for resource in &package.resources {
+            let key = format!(
+                "{} {}/{}",
+                resource.kind,
+                resource.namespace.as_deref().unwrap_or_default(),
+                resource.name
+            );
+            resources.insert(key, resource.manifest.as_deref().unwrap_or_default());
+        }
+    }
+    packages
+}
+
+// pads the cells of every column to the same width
+fn align(rows: &[Vec<String>]) -> String {
+    let mut widths = vec![0; rows.first().map_or(0, Vec::len)];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let mut table = String::new();
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+// renders the reports of the upstreams of a run, a single report as it is,
+// several as a list or one table after the other
+pub fn render_all(reports: &mut [Report], format: Format) -> String {
+    if let [report] = reports {
+        return report.render(format);
+    }
+    reports.iter_mut().for_each(Report::sort);
+    match format {
+        Format::Json => serde_json::to_string_pretty(reports).unwrap() + "\n",
+        Format::Yaml => serde_yaml::to_string(reports).unwrap(),
+        Format::Table => reports.iter().map(Report::table).collect::<Vec<String>>().join("\n"),
+    }
+}
+
+pub fn write_all(reports: &mut [Report], path: &Path) -> std::io::Result<()> {
+    std::fs::write(path, render_all(reports, Format::Json))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Format {
+    #[default]
+    Table,
+    Json,
+    Yaml,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Format, String> {
+        match format {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "yaml" => Ok(Format::Yaml),
+            _ => Err(format!("unknown output format {}, use json, yaml or table", format)),
+        }
+    }
+}
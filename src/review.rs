@@ -0,0 +1,151 @@
+use crate::{Config, Resource};
+use std::io::{BufRead, Write};
+
+// package chosen during the review for a resource or for all resources of
+// a kind, written back as a split rule in front of the existing ones
+pub struct Assignment {
+    kind: String,
+    // unset for all resources of the kind
+    name: Option<String>,
+    namespace: Option<String>,
+    package: String,
+}
+
+impl Assignment {
+    fn matches(&self, resource: &Resource) -> bool {
+        self.kind == resource.kind
+            && self.name.as_ref().is_none_or(|name| name == &resource.name)
+            && (self.name.is_none() || self.namespace == resource.namespace)
+    }
+
+    // the split rule as yaml lines, without indentation
+    fn rule(&self) -> Vec<String> {
+        let mut lines = vec!["- matcher:".to_string(), format!("    kind: {}", scalar(&self.kind))];
+        if let Some(name) = &self.name {
+            lines.push(format!("    name: {}", scalar(name)));
+            if let Some(namespace) = &self.namespace {
+                lines.push(format!("    namespace: {}", scalar(namespace)));
+            }
+        }
+        lines.push(format!("  packageName: {}", scalar(&self.package)));
+        lines
+    }
+}
+
+fn scalar(value: &str) -> String {
+    serde_yaml::to_string(value).unwrap().trim_start_matches("---").trim().to_string()
+}
+
+// walks through the resources showing the package each one is placed in,
+// reads reassignments from input, returns them unless the review is aborted
+pub fn review(
+    config: &Config,
+    resources: &[Resource],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> std::io::Result<Option<Vec<Assignment>>> {
+    let mut assignments: Vec<Assignment> = Vec::new();
+    writeln!(
+        output,
+        "enter to keep, p <package> to move the resource, k <package> to move all resources of its kind,\nq to finish and write the rules, x to exit without writing"
+    )?;
+    for (idx, resource) in resources.iter().enumerate() {
+        let placement = placement(config, &assignments, resource);
+        write!(
+            output,
+            "[{}/{}] {} {}/{} -> {} ",
+            idx + 1,
+            resources.len(),
+            resource.kind,
+            resource.namespace.clone().unwrap_or_default(),
+            resource.name,
+            placement
+        )?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            break;
+        }
+        let answer = answer.trim();
+        let (command, package) = answer.split_once(' ').unwrap_or((answer, ""));
+        let package = package.trim().to_string();
+        match command {
+            "" => {}
+            "q" => break,
+            "x" => return Ok(None),
+            "p" | "k" if !package.is_empty() => {
+                let name = (command == "p").then(|| resource.name.clone());
+                let assignment = Assignment {
+                    kind: resource.kind.clone(),
+                    namespace: resource.namespace.clone(),
+                    name,
+                    package,
+                };
+                // a later choice for the same resources replaces the earlier one
+                assignments.retain(|other| {
+                    other.kind != assignment.kind || other.name != assignment.name || other.namespace != assignment.namespace
+                });
+                assignments.push(assignment);
+            }
+            _ => writeln!(output, "unknown answer {}, keeping the resource where it is", answer)?,
+        }
+    }
+    Ok(Some(assignments))
+}
+
+// packages of the resource and the rule deciding on them
+fn placement(config: &Config, assignments: &[Assignment], resource: &Resource) -> String {
+    let ordered = assignments
+        .iter()
+        .filter(|assignment| assignment.name.is_some())
+        .chain(assignments.iter().filter(|assignment| assignment.name.is_none()));
+    if let Some(assignment) = ordered.into_iter().find(|assignment| assignment.matches(resource)) {
+        return format!("{} (reviewed)", assignment.package);
+    }
+    let placements = match config.classify(resource) {
+        Ok(placements) => placements,
+        Err(err) => return format!("unclassified ({})", err),
+    };
+    let packages: Vec<&str> = placements.iter().map(|placement| placement.package_name.as_str()).collect();
+    let rule = match placements.first().and_then(|placement| placement.rule_idx) {
+        Some(rule_idx) => format!("SplitRules[{}]", rule_idx),
+        None => "default package".to_string(),
+    };
+    match packages.is_empty() {
+        true => format!("dropped ({})", rule),
+        false => format!("{} ({})", packages.join(", "), rule),
+    }
+}
+
+// inserts the rules of the assignments at the start of the SplitRules of a
+// yaml config, rules for single resources before those for whole kinds
+pub fn insert_rules(config: &str, assignments: &[Assignment]) -> Result<String, String> {
+    let mut lines: Vec<String> = config.lines().map(String::from).collect();
+    let start = lines
+        .iter()
+        .position(|line| line.starts_with("SplitRules:"))
+        .ok_or_else(|| "the config has no SplitRules".to_string())?;
+    // the indentation of the existing items, two spaces if there are none
+    let indent = match lines[start].trim_end() {
+        "SplitRules:" => lines[start + 1..]
+            .iter()
+            .find(|line| line.trim_start().starts_with('-'))
+            .map(|line| line.len() - line.trim_start().len())
+            .unwrap_or(2),
+        "SplitRules: []" => {
+            lines[start] = "SplitRules:".to_string();
+            2
+        }
+        _ => return Err("only block style SplitRules can be edited".to_string()),
+    };
+    let ordered = assignments
+        .iter()
+        .filter(|assignment| assignment.name.is_some())
+        .chain(assignments.iter().filter(|assignment| assignment.name.is_none()));
+    let rules: Vec<String> = ordered
+        .flat_map(Assignment::rule)
+        .map(|line| format!("{}{}", " ".repeat(indent), line))
+        .collect();
+    lines.splice(start + 1..start + 1, rules);
+    Ok(lines.join("\n") + "\n")
+}
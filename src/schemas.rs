@@ -0,0 +1,142 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+// at most this many problems are named per manifest
+const MAX_PROBLEMS: usize = 5;
+
+// offline validation of the manifests against kubernetes json schemas, e.g.
+// a checkout of kubernetes-json-schema and of the CRDs-catalog
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SchemaValidation {
+    // directories with the schemas of the built-in kinds, laid out as
+    // v<version>-standalone-strict/<kind>-<group>-<version>.json for the
+    // KubernetesVersion of the config or master-standalone-strict without,
+    // relative to the config
+    #[serde(default)]
+    pub schemaDirs: Vec<PathBuf>,
+    // directories with the schemas of custom resources, laid out as
+    // <group>/<kind>_<version>.json, relative to the config
+    #[serde(default)]
+    pub crdSchemaDirs: Vec<PathBuf>,
+    // resources without a schema pass instead of being invalid
+    #[serde(default)]
+    pub ignoreMissingSchemas: bool,
+    #[serde(default)]
+    pub onInvalid: OnInvalid,
+}
+
+// what an invalid manifest does to the run
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum OnInvalid {
+    // fail the resource like any other error, --keep-going writes the others
+    #[default]
+    Fail,
+    // log a warning and write the manifest
+    Warn,
+}
+
+impl SchemaValidation {
+    // the schema directories are relative to the config file
+    pub fn resolve_dirs(&mut self, base_dir: &Path) {
+        for dir in self.schemaDirs.iter_mut().chain(self.crdSchemaDirs.iter_mut()) {
+            *dir = base_dir.join(&*dir);
+        }
+    }
+}
+
+// validates manifests, the schemas are compiled once per apiVersion and
+// kind, None if there is none
+pub struct SchemaValidator {
+    // e.g. v1.29.0-standalone-strict
+    version_dir: String,
+    validators: HashMap<(String, String), Option<jsonschema::Validator>>,
+}
+
+impl SchemaValidator {
+    // the built-in kinds are validated against the schemas of the kubernetes
+    // version, e.g. 1.29 or 1.29.3, the latest ones if it is unset
+    pub fn new(kubernetes_version: Option<&str>) -> SchemaValidator {
+        let version_dir = match kubernetes_version.map(|version| version.trim_start_matches('v')) {
+            None => "master-standalone-strict".to_string(),
+            Some(version) if version.matches('.').count() == 1 => format!("v{}.0-standalone-strict", version),
+            Some(version) => format!("v{}-standalone-strict", version),
+        };
+        SchemaValidator {
+            version_dir,
+            validators: HashMap::new(),
+        }
+    }
+
+    // the problems of a manifest, empty if it is valid
+    pub fn validate(&mut self, spec: &SchemaValidation, manifest: &serde_json::Value) -> Result<Vec<String>, String> {
+        let api_version = manifest["apiVersion"].as_str().unwrap_or_default().to_string();
+        let kind = manifest["kind"].as_str().unwrap_or_default().to_string();
+        let key = (api_version, kind);
+        if !self.validators.contains_key(&key) {
+            let validator = load_validator(spec, &self.version_dir, &key.0, &key.1)?;
+            self.validators.insert(key.clone(), validator);
+        }
+        let validator = match &self.validators[&key] {
+            Some(validator) => validator,
+            None if spec.ignoreMissingSchemas => return Ok(Vec::new()),
+            None => return Ok(vec![format!("no schema for {} {}", key.0, key.1)]),
+        };
+        let mut problems: Vec<String> = validator
+            .iter_errors(manifest)
+            .map(|err| match err.instance_path.as_str() {
+                "" => err.to_string(),
+                path => format!("{}: {}", path, err),
+            })
+            .collect();
+        if problems.len() > MAX_PROBLEMS {
+            let more = problems.len() - MAX_PROBLEMS;
+            problems.truncate(MAX_PROBLEMS);
+            problems.push(format!("and {} more", more));
+        }
+        Ok(problems)
+    }
+}
+
+// the schema of the first file found for an apiVersion and kind, the
+// built-in schemas name the group by its first label, e.g. networking
+fn load_validator(
+    spec: &SchemaValidation,
+    version_dir: &str,
+    api_version: &str,
+    kind: &str,
+) -> Result<Option<jsonschema::Validator>, String> {
+    let (group, version) = api_version.rsplit_once('/').unwrap_or(("", api_version));
+    let file_kind = kind.to_lowercase();
+    let builtin = match group.split('.').next().unwrap_or_default() {
+        "" => format!("{}-{}.json", file_kind, version),
+        short_group => format!("{}-{}-{}.json", file_kind, short_group, version),
+    };
+    let candidates = spec
+        .schemaDirs
+        .iter()
+        .map(|dir| dir.join(version_dir).join(&builtin))
+        .chain(
+            spec.crdSchemaDirs
+                .iter()
+                .map(|dir| dir.join(group).join(format!("{}_{}.json", file_kind, version))),
+        );
+    for path in candidates {
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+        debug!("validate {} {} with {}", api_version, kind, path.display());
+        let schema: serde_json::Value =
+            serde_json::from_str(&text).map_err(|err| format!("unable to parse schema {}: {}", path.display(), err))?;
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|err| format!("unable to compile schema {}: {}", path.display(), err))?;
+        return Ok(Some(validator));
+    }
+    Ok(None)
+}
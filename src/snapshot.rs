@@ -0,0 +1,151 @@
+use crate::Error;
+use similar::TextDiff;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// list of the files of a snapshot relative to its directory, only these are
+// verified so a snapshot can live next to other files
+const RECORD_FILENAME: &str = ".kustomize-upstream-snapshot";
+
+// writes the generated files into a snapshot directory, replacing a previous
+// snapshot there, the paths are relative to the snapshot directory
+pub fn record(dir: &Path, files: &[(PathBuf, &[u8])], force: bool) -> Result<(), Error> {
+    let previous = match read_record(dir) {
+        Some(previous) => previous,
+        None if force || fs::read_dir(dir).map_or(true, |mut entries| entries.next().is_none()) => BTreeSet::new(),
+        None => {
+            return Err(Error::Usage(format!(
+                "{} is not empty and holds no snapshot, use --force to record into it",
+                dir.display()
+            )))
+        }
+    };
+    for (path, content) in files {
+        let filepath = dir.join(path);
+        if let Some(parent) = filepath.parent() {
+            fs::create_dir_all(parent).map_err(Error::file("create", parent))?;
+        }
+        fs::write(&filepath, content).map_err(Error::file("write", &filepath))?;
+    }
+    // files of the previous snapshot which are no longer generated
+    let paths: BTreeSet<&PathBuf> = files.iter().map(|(path, _)| path).collect();
+    for path in previous.iter().filter(|path| !paths.contains(path)) {
+        let filepath = dir.join(path);
+        if filepath.is_file() {
+            fs::remove_file(&filepath).map_err(Error::file("delete", &filepath))?;
+        }
+    }
+    let lines: Vec<String> = paths.iter().map(|path| path.display().to_string() + "\n").collect();
+    let record = dir.join(RECORD_FILENAME);
+    fs::write(&record, lines.concat()).map_err(Error::file("write", &record))
+}
+
+// prints a unified diff of every generated file which differs from the
+// snapshot and returns the differences, empty if the snapshot matches
+pub fn verify(dir: &Path, files: &[(PathBuf, &[u8])]) -> Result<Vec<String>, Error> {
+    let recorded = read_record(dir).ok_or_else(|| Error::Usage(format!("{} holds no snapshot", dir.display())))?;
+    let mut differences = Vec::new();
+    for (path, content) in files {
+        let old = match recorded.contains(path) {
+            true => Some(fs::read(dir.join(path)).map_err(Error::file("read", dir.join(path)))?),
+            false => None,
+        };
+        if old.as_deref() == Some(*content) {
+            continue;
+        }
+        let state = if old.is_some() { "changed" } else { "new" };
+        differences.push(format!("{}: {}", state, path.display()));
+        print_diff(path, old.as_deref(), Some(content));
+    }
+    let paths: BTreeSet<&PathBuf> = files.iter().map(|(path, _)| path).collect();
+    for path in recorded.iter().filter(|path| !paths.contains(path)) {
+        let old = fs::read(dir.join(path)).map_err(Error::file("read", dir.join(path)))?;
+        differences.push(format!("removed: {}", path.display()));
+        print_diff(path, Some(&old), None);
+    }
+    Ok(differences)
+}
+
+fn read_record(dir: &Path) -> Option<BTreeSet<PathBuf>> {
+    let record = fs::read_to_string(dir.join(RECORD_FILENAME)).ok()?;
+    Some(record.lines().map(PathBuf::from).collect())
+}
+
+// prints the unified diff of a file in the snapshot and as generated, None
+// if it is missing on either side
+fn print_diff(path: &Path, old: Option<&[u8]>, new: Option<&[u8]>) {
+    let header = |prefix: &str, content: Option<&[u8]>| match content {
+        Some(_) => format!("{}/{}", prefix, path.display()),
+        None => "/dev/null".to_string(),
+    };
+    let (old_header, new_header) = (header("a", old), header("b", new));
+    let old = String::from_utf8_lossy(old.unwrap_or_default());
+    let new = String::from_utf8_lossy(new.unwrap_or_default());
+    let diff = TextDiff::from_lines(old.as_ref(), new.as_ref());
+    print!("{}", diff.unified_diff().header(&old_header, &new_header));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{split_manifests, Config};
+
+    // upstream, config and recorded snapshot of a small split
+    const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/snapshot");
+
+    fn split_fixture() -> Vec<(PathBuf, Vec<u8>)> {
+        let fixture_dir = Path::new(FIXTURE_DIR);
+        let config = Config::load(&fixture_dir.join("config.yaml"), None, &[]).unwrap();
+        let upstream = fs::read(fixture_dir.join("upstream.yaml")).unwrap();
+        split_manifests(&upstream[..], &config).unwrap().files
+    }
+
+    fn borrowed(files: &[(PathBuf, Vec<u8>)]) -> Vec<(PathBuf, &[u8])> {
+        files.iter().map(|(path, content)| (path.clone(), content.as_slice())).collect()
+    }
+
+    #[test]
+    fn split_matches_the_golden_snapshot() {
+        let files = split_fixture();
+        let differences = verify(&Path::new(FIXTURE_DIR).join("golden"), &borrowed(&files)).unwrap();
+        assert!(differences.is_empty(), "{:?}", differences);
+    }
+
+    #[test]
+    fn verify_reports_changed_new_and_removed_files() {
+        let files = split_fixture();
+        let dir = tempfile::tempdir().unwrap();
+        record(dir.path(), &borrowed(&files), false).unwrap();
+        assert_eq!(verify(dir.path(), &borrowed(&files)).unwrap(), Vec::<String>::new());
+
+        let mut changed = files.clone();
+        let last = changed[0].1.len() - 1;
+        changed[0].1[last] = b' ';
+        let expected = vec![format!("changed: {}", files[0].0.display())];
+        assert_eq!(verify(dir.path(), &borrowed(&changed)).unwrap(), expected);
+
+        let mut extra = files.clone();
+        extra.push((PathBuf::from("widgets/extra.yaml"), b"kind: Extra\n".to_vec()));
+        assert_eq!(verify(dir.path(), &borrowed(&extra)).unwrap(), vec!["new: widgets/extra.yaml"]);
+
+        let missing = files[1..].to_vec();
+        let expected = vec![format!("removed: {}", files[0].0.display())];
+        assert_eq!(verify(dir.path(), &borrowed(&missing)).unwrap(), expected);
+
+        // recording again drops the files no longer generated
+        record(dir.path(), &borrowed(&missing), false).unwrap();
+        assert!(!dir.path().join(&files[0].0).exists());
+        assert_eq!(verify(dir.path(), &borrowed(&missing)).unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn record_refuses_a_directory_without_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("README.md"), "hand written\n").unwrap();
+        assert!(matches!(record(dir.path(), &[], false), Err(Error::Usage(_))));
+        assert!(matches!(verify(dir.path(), &[]), Err(Error::Usage(_))));
+        record(dir.path(), &[], true).unwrap();
+        assert!(dir.path().join("README.md").exists());
+    }
+}
@@ -0,0 +1,114 @@
+use crate::init::SUGGESTED_PACKAGES;
+use std::collections::BTreeMap;
+use yaml_rust::Yaml;
+
+// labels commonly naming the part of a project a resource belongs to, in the
+// order they are preferred for splitting
+const COMMON_LABELS: &[&str] = &[
+    "app.kubernetes.io/component",
+    "app.kubernetes.io/name",
+    "app.kubernetes.io/part-of",
+    "app",
+];
+
+// candidate split rules for the manifests as yaml, every rule commented with
+// the resources it would place given the rules before it, alternatives
+// which would replace a rule are commented out
+pub fn suggest_rules(manifests: &[Yaml]) -> String {
+    let resources: Vec<&Yaml> = manifests
+        .iter()
+        .filter(|manifest| manifest["kind"].as_str().is_some() && manifest["metadata"]["name"].as_str().is_some())
+        .collect();
+    let mut remaining: Vec<&Yaml> = resources.clone();
+    let mut suggestion = format!("# {} resources\nSplitRules:\n", resources.len());
+
+    for (package, kinds) in SUGGESTED_PACKAGES {
+        for kind in kinds.iter() {
+            let count = take(&mut remaining, |manifest| manifest["kind"].as_str() == Some(kind));
+            if count == 0 {
+                continue;
+            }
+            suggestion.push_str(&format!(
+                "  # {} {}: {} resources\n  - matcher:\n      kind: {}\n    packageName: {}\n",
+                package,
+                kind,
+                count,
+                kind,
+                package
+            ));
+        }
+    }
+
+    // labels with at least two values among the remaining resources
+    let mut candidates: Vec<(&str, BTreeMap<String, usize>)> = COMMON_LABELS
+        .iter()
+        .map(|label| (*label, values(&remaining, |manifest| label_value(manifest, label))))
+        .filter(|(_, values)| values.len() > 1)
+        .collect();
+    let namespaces = values(&remaining, |manifest| manifest["metadata"]["namespace"].as_str().map(String::from));
+    if namespaces.len() > 1 {
+        candidates.push(("namespace", namespaces));
+    }
+    for (idx, (label, values)) in candidates.iter().enumerate() {
+        let covered: usize = values.values().sum();
+        let packages: Vec<String> = values.iter().map(|(value, count)| format!("{} {}", value, count)).collect();
+        let (alternative, prefix) = match idx {
+            0 => ("", "  "),
+            _ => (" (alternative)", "  # "),
+        };
+        suggestion.push_str(&format!(
+            "  # by {}{}: {} resources in {} packages ({})\n",
+            label,
+            alternative,
+            covered,
+            values.len(),
+            packages.join(", ")
+        ));
+        let rule = match *label {
+            "namespace" => "- splitBy: namespace\n".to_string(),
+            label => format!(
+                "- classify: |\n    let labels = manifest.metadata.labels;\n    if type_of(labels) != \"map\" {{ return; }}\n    labels[\"{}\"]\n",
+                label
+            ),
+        };
+        for line in rule.lines() {
+            suggestion.push_str(prefix);
+            suggestion.push_str(line);
+            suggestion.push('\n');
+        }
+        if idx == 0 {
+            take(&mut remaining, |manifest| match *label {
+                "namespace" => manifest["metadata"]["namespace"].as_str().is_some(),
+                label => label_value(manifest, label).is_some(),
+            });
+        }
+    }
+    suggestion.push_str(&format!(
+        "# {} of {} resources are left for the default package\n",
+        remaining.len(),
+        resources.len()
+    ));
+    suggestion
+}
+
+// removes the matching resources, returns how many were removed
+fn take(remaining: &mut Vec<&Yaml>, matches: impl Fn(&Yaml) -> bool) -> usize {
+    let before = remaining.len();
+    remaining.retain(|manifest| !matches(manifest));
+    before - remaining.len()
+}
+
+// number of resources per value
+fn values(resources: &[&Yaml], value: impl Fn(&Yaml) -> Option<String>) -> BTreeMap<String, usize> {
+    let mut values: BTreeMap<String, usize> = BTreeMap::new();
+    for resource in resources {
+        if let Some(value) = value(resource) {
+            *values.entry(value).or_default() += 1;
+        }
+    }
+    values
+}
+
+fn label_value(manifest: &Yaml, label: &str) -> Option<String> {
+    manifest["metadata"]["labels"][label].as_str().map(String::from)
+}
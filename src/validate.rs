@@ -0,0 +1,250 @@
+use crate::error::describe;
+use crate::{deprecations, Config, Package, PackageSpec, ResourceSpec, Resource};
+use std::collections::{BTreeMap, HashMap};
+use tera::Context;
+use yaml_rust::YamlLoader;
+
+// manifest the templates are test-rendered with
+const SAMPLE_MANIFEST: &str = "
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: example
+  namespace: default
+  labels:
+    app: example
+data:
+  key: value
+";
+
+// returns the problems found in a parsed config, empty if it is valid
+pub fn validate(config: &Config) -> Vec<String> {
+    let mut problems = check_rules(config);
+    problems.extend(check_kubernetes_version(config));
+    problems.extend(check_schema_dirs(config));
+    problems.extend(check_patches(config));
+    let context = sample_context(config);
+    for (name, template) in templates(config) {
+        let mut tera = config.tera.clone();
+        if let Err(err) = tera.add_raw_template(&name, &template) {
+            problems.push(format!("{}: {}", name, describe(&err)));
+            continue;
+        }
+        if let Err(err) = tera.render(&name, &context) {
+            problems.push(format!("{}: unable to render with a sample resource: {}", name, describe(&err)));
+        }
+    }
+    problems
+}
+
+// package specs referenced by the rules must exist and every package must
+// be given the same package spec by all rules naming it
+pub fn check_rules(config: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut package_specs: BTreeMap<String, (usize, Option<&String>)> = BTreeMap::new();
+    for (rule_idx, rule) in config.SplitRules.iter().enumerate() {
+        if let Some(spec) = &rule.packageSpec {
+            if !config.PackageSpecs.contains_key(spec) {
+                problems.push(format!("SplitRules[{}]: unknown package spec {}", rule_idx, spec));
+            }
+        }
+        for package_name in rule.package_names() {
+            let spec = rule.packageSpec.as_ref();
+            match package_specs.get(&package_name) {
+                Some((other_idx, other_spec)) if *other_spec != spec => problems.push(format!(
+                    "SplitRules[{}]: package {} gets package spec {} but SplitRules[{}] gives it {}",
+                    rule_idx,
+                    package_name,
+                    spec.map_or("DefaultPackageSpec", String::as_str),
+                    other_idx,
+                    other_spec.map_or("DefaultPackageSpec", String::as_str),
+                )),
+                Some(_) => {}
+                None => {
+                    package_specs.insert(package_name, (rule_idx, spec));
+                }
+            }
+        }
+    }
+    problems
+}
+
+// the kubernetes version selects the deprecations and the schemas
+pub fn check_kubernetes_version(config: &Config) -> Option<String> {
+    let version = config.KubernetesVersion.as_ref()?;
+    match deprecations::parse_version(version) {
+        Some(_) => None,
+        None => Some(format!("KubernetesVersion {}: expected a version like 1.29", version)),
+    }
+}
+
+// the schema directories must exist, a missing one would leave every
+// resource without a schema
+fn check_schema_dirs(config: &Config) -> Vec<String> {
+    let validation = match &config.SchemaValidation {
+        Some(validation) => validation,
+        None => return Vec::new(),
+    };
+    let dirs = [("schemaDirs", &validation.schemaDirs), ("crdSchemaDirs", &validation.crdSchemaDirs)];
+    dirs.iter()
+        .flat_map(|(field, dirs)| dirs.iter().map(move |dir| (field, dir)))
+        .filter(|(_, dir)| !dir.is_dir())
+        .map(|(field, dir)| format!("SchemaValidation.{}: {} is no directory", field, dir.display()))
+        .collect()
+}
+
+// patches given as yaml strings must parse
+fn check_patches(config: &Config) -> Vec<String> {
+    config
+        .Patches
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, rule)| rule.check().err().map(|err| format!("Patches[{}]: {}", idx, err)))
+        .collect()
+}
+
+// every template of the config with the name it is rendered under
+pub fn templates(config: &Config) -> Vec<(String, String)> {
+    let mut templates = vec![("Top.sourceTemplate".to_string(), config.Top.sourceTemplate.clone())];
+    if let Some(template) = &config.Top.mirrorPathTemplate {
+        templates.push(("Top.mirrorPathTemplate".to_string(), template.clone()));
+    }
+    if let Some(changelog) = &config.Changelog {
+        templates.push(("Changelog.pathTemplate".to_string(), changelog.pathTemplate.clone()));
+    }
+    package_spec_templates("DefaultPackageSpec", &config.DefaultPackageSpec.spec, &mut templates);
+    for (name, spec) in &config.PackageSpecs {
+        package_spec_templates(&format!("PackageSpecs.{}", name), spec, &mut templates);
+    }
+    for (rule_idx, rule) in config.SplitRules.iter().enumerate() {
+        let prefix = format!("SplitRules[{}]", rule_idx);
+        if let Some(template) = &rule.packageNameTemplate {
+            templates.push((format!("{}.packageNameTemplate", prefix), template.clone()));
+        }
+        if let Some(resource_spec) = &rule.resourceSpec {
+            resource_spec_templates(&format!("{}.resourceSpec", prefix), resource_spec, &mut templates);
+        }
+        if let Some(rename) = &rule.rename {
+            templates.push((format!("{}.rename.nameTemplate", prefix), rename.nameTemplate.clone()));
+        }
+        for (field, entries) in [("labels", &rule.labels), ("annotations", &rule.annotations)] {
+            for (key, template) in entries {
+                templates.push((format!("{}.{}.{}", prefix, field, key), template.clone()));
+            }
+        }
+    }
+    if let Some(root_spec) = &config.RootSpec {
+        templates.push(("RootSpec.template".to_string(), root_spec.template.clone()));
+        templates.push(("RootSpec.filenameTemplate".to_string(), root_spec.filenameTemplate.clone()));
+        templates.push(("RootSpec.pathTemplate".to_string(), root_spec.pathTemplate.clone()));
+    }
+    for (name, overlay) in &config.Overlays {
+        let prefix = format!("Overlays.{}", name);
+        templates.push((format!("{}.template", prefix), overlay.template.clone()));
+        templates.push((format!("{}.filenameTemplate", prefix), overlay.filenameTemplate.clone()));
+        templates.push((format!("{}.pathTemplate", prefix), overlay.pathTemplate.clone()));
+        for (idx, patch) in overlay.patches.iter().enumerate() {
+            templates.push((format!("{}.patches[{}].template", prefix, idx), patch.template.clone()));
+            templates.push((
+                format!("{}.patches[{}].filenameTemplate", prefix, idx),
+                patch.filenameTemplate.clone(),
+            ));
+        }
+    }
+    for (idx, rule) in config.Generators.iter().enumerate() {
+        templates.push((format!("Generators[{}].dirTemplate", idx), rule.dirTemplate.clone()));
+    }
+    if let Some(flux) = &config.Flux {
+        for (field, template) in flux.templates() {
+            templates.push((format!("Flux.{}", field), template.to_string()));
+        }
+    }
+    if let Some(argocd) = &config.ArgoCD {
+        for (field, template) in argocd.templates() {
+            templates.push((format!("ArgoCD.{}", field), template.to_string()));
+        }
+    }
+    templates
+}
+
+fn package_spec_templates(prefix: &str, spec: &PackageSpec, templates: &mut Vec<(String, String)>) {
+    templates.push((format!("{}.template", prefix), spec.template.clone()));
+    templates.push((format!("{}.filenameTemplate", prefix), spec.filenameTemplate.clone()));
+    templates.push((format!("{}.pathTemplate", prefix), spec.pathTemplate.clone()));
+    resource_spec_templates(&format!("{}.resourceSpec", prefix), &spec.resourceSpec, templates);
+    for (idx, extra_file) in spec.extraFiles.iter().enumerate() {
+        let extra_prefix = format!("{}.extraFiles[{}]", prefix, idx);
+        templates.push((format!("{}.template", extra_prefix), extra_file.template.clone()));
+        templates.push((format!("{}.filenameTemplate", extra_prefix), extra_file.filenameTemplate.clone()));
+    }
+    for (field, entries) in [("commonLabels", &spec.commonLabels), ("commonAnnotations", &spec.commonAnnotations)] {
+        for (key, template) in entries {
+            templates.push((format!("{}.{}.{}", prefix, field, key), template.clone()));
+        }
+    }
+    for (field, template) in [("namePrefix", &spec.namePrefix), ("nameSuffix", &spec.nameSuffix)] {
+        if let Some(template) = template {
+            templates.push((format!("{}.{}", prefix, field), template.clone()));
+        }
+    }
+    if let Some(patch_stubs) = &spec.patchStubs {
+        templates.push((
+            format!("{}.patchStubs.filenameTemplate", prefix),
+            patch_stubs.filenameTemplate.clone(),
+        ));
+    }
+}
+
+fn resource_spec_templates(prefix: &str, spec: &ResourceSpec, templates: &mut Vec<(String, String)>) {
+    templates.push((format!("{}.filenameTemplate", prefix), spec.filenameTemplate.clone()));
+    templates.push((format!("{}.pathTemplate", prefix), spec.pathTemplate.clone()));
+    if let Some(template) = spec.group_filename_template() {
+        templates.push((format!("{}.groupFilenameTemplate", prefix), template.to_string()));
+    }
+}
+
+// context with every variable any template is rendered with, filled with a
+// sample resource in a sample package
+fn sample_context(config: &Config) -> Context {
+    let manifest = YamlLoader::load_from_str(SAMPLE_MANIFEST).unwrap().remove(0);
+    let resource = Resource::from_manifest(&manifest, 0).unwrap().unwrap();
+    let mut package = Package {
+        name: config.DefaultPackageSpec.defaultName.clone(),
+        path: Some(config.DefaultPackageSpec.defaultName.clone()),
+        filename: Some("kustomization.yaml".to_string()),
+        relativePath: Some(config.DefaultPackageSpec.defaultName.clone()),
+        resources: vec![resource.clone()],
+        ..Default::default()
+    };
+    package.aggregate();
+    package.set_component(false);
+
+    // the variables of all specs and rules, as any of them may be in scope
+    let mut vars: HashMap<String, tera::Value> = config.Variables.clone();
+    vars.extend(config.DefaultPackageSpec.spec.vars.clone());
+    for spec in config.PackageSpecs.values() {
+        vars.extend(spec.vars.clone());
+    }
+    for rule in &config.SplitRules {
+        vars.extend(rule.vars.clone());
+    }
+    for overlay in config.Overlays.values() {
+        vars.extend(overlay.vars.clone());
+    }
+
+    let mut context = Context::new();
+    context.insert("top", &config.Top);
+    context.insert("vars", &vars);
+    context.insert("packageName", &package.name);
+    context.insert("resource", &resource);
+    context.insert("namePrefix", "");
+    context.insert("nameSuffix", "");
+    context.insert("commonLabels", &BTreeMap::<String, String>::new());
+    context.insert("commonAnnotations", &BTreeMap::<String, String>::new());
+    context.insert("images", &Vec::<String>::new());
+    context.insert("overlay", &HashMap::from([("name", "example")]));
+    context.insert("patches", &Vec::<String>::new());
+    context.insert("package", &package);
+    crate::insert_packages(&mut context, &[package]);
+    context
+}